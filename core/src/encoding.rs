@@ -0,0 +1,302 @@
+use alloc::vec::Vec;
+use core::fmt;
+use sha3::{Digest, Sha3_256};
+
+use crate::{IndexError, QuadTreeIndex, QuadTreeMembershipProof};
+
+/// First bytes of every encoded membership proof
+pub const PROOF_MAGIC: [u8; 4] = *b"QTMP";
+/// Wire format version written by `to_bytes`
+pub const PROOF_FORMAT_VERSION: u8 = 1;
+
+/// Magic, version and depth
+const HEADER_SIZE: usize = PROOF_MAGIC.len() + 2;
+/// Truncated SHA3-256 over everything before it
+const CHECKSUM_SIZE: usize = 4;
+
+/// Reasons `QuadTreeMembershipProof::from_bytes` rejects its input
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum DecodeError {
+    /// Input length does not match the depth in the header
+    Length {
+        expected: usize,
+        actual: usize,
+    },
+    BadMagic,
+    UnsupportedVersion(u8),
+    /// Depth 0 is a bare root with no leaf to prove
+    ZeroDepth,
+    ChecksumMismatch,
+    /// Unused low bits of the packed leaf path are not zero
+    PathPadding,
+}
+
+impl fmt::Display for DecodeError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::Length { expected, actual } => {
+                write!(f, "expected {} bytes, got {}", expected, actual)
+            }
+            Self::BadMagic => write!(f, "not a quad tree membership proof"),
+            Self::UnsupportedVersion(version) => {
+                write!(f, "unsupported proof format version {}", version)
+            }
+            Self::ZeroDepth => write!(f, "proof depth must be at least 1"),
+            Self::ChecksumMismatch => write!(f, "proof checksum mismatch"),
+            Self::PathPadding => write!(f, "nonzero padding bits in the packed leaf path"),
+        }
+    }
+}
+
+/// Reasons `QuadTreeMembershipProof::to_bytes` refuses to encode a proof
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum EncodeError {
+    /// `leaf_index` is not a valid index (see `QuadTreeIndex::try_new`)
+    Index(IndexError),
+    /// The proof does not carry one sibling triple per level
+    SiblingCountMismatch { depth: u8, sibling_levels: usize },
+}
+
+impl fmt::Display for EncodeError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::Index(err) => write!(f, "invalid leaf index: {}", err),
+            Self::SiblingCountMismatch {
+                depth,
+                sibling_levels,
+            } => write!(
+                f,
+                "proof has {} sibling levels for a depth {} leaf",
+                sibling_levels, depth
+            ),
+        }
+    }
+}
+
+/// Size of an encoded membership proof for a tree of the given depth
+pub fn encoded_proof_size(depth: u8) -> usize {
+    let depth = depth as usize;
+    HEADER_SIZE + depth.div_ceil(4) + 32 + 32 + depth * 3 * 32 + CHECKSUM_SIZE
+}
+
+impl QuadTreeMembershipProof {
+    /// Encode in the versioned binary wire format
+    ///
+    /// Layout (v1): magic `QTMP`, version, depth, path packed 2 bits per level
+    /// (`QuadTreeIndex::pack`), leaf hash, root hash, `depth` sibling triples
+    /// LEAF to ROOT, then the first 4 bytes of SHA3-256 over all of the above.
+    ///
+    /// Fails on a malformed proof (bad index or wrong sibling count) instead
+    /// of writing bytes that `from_bytes` would decode as a different proof.
+    pub fn to_bytes(&self) -> Result<Vec<u8>, EncodeError> {
+        let depth = self.leaf_index.depth;
+        let packed_path = self.leaf_index.pack().map_err(EncodeError::Index)?;
+        if self.sibling_hashes.len() != depth as usize {
+            return Err(EncodeError::SiblingCountMismatch {
+                depth,
+                sibling_levels: self.sibling_hashes.len(),
+            });
+        }
+
+        let mut bytes = Vec::with_capacity(encoded_proof_size(depth));
+        bytes.extend_from_slice(&PROOF_MAGIC);
+        bytes.push(PROOF_FORMAT_VERSION);
+        bytes.push(depth);
+        bytes.extend_from_slice(&packed_path);
+        bytes.extend_from_slice(&self.leaf_hash);
+        bytes.extend_from_slice(&self.root_hash);
+        for siblings in &self.sibling_hashes {
+            for sibling in siblings {
+                bytes.extend_from_slice(sibling);
+            }
+        }
+
+        let checksum = checksum(&bytes);
+        bytes.extend_from_slice(&checksum);
+        Ok(bytes)
+    }
+
+    /// Decode a proof written by `to_bytes`
+    pub fn from_bytes(bytes: &[u8]) -> Result<Self, DecodeError> {
+        if bytes.len() < HEADER_SIZE {
+            return Err(DecodeError::Length {
+                expected: HEADER_SIZE,
+                actual: bytes.len(),
+            });
+        }
+        if bytes[..PROOF_MAGIC.len()] != PROOF_MAGIC {
+            return Err(DecodeError::BadMagic);
+        }
+        let version = bytes[PROOF_MAGIC.len()];
+        if version != PROOF_FORMAT_VERSION {
+            return Err(DecodeError::UnsupportedVersion(version));
+        }
+
+        let depth = bytes[PROOF_MAGIC.len() + 1];
+        if depth == 0 {
+            return Err(DecodeError::ZeroDepth);
+        }
+        let expected = encoded_proof_size(depth);
+        if bytes.len() != expected {
+            return Err(DecodeError::Length {
+                expected,
+                actual: bytes.len(),
+            });
+        }
+
+        let (body, stored_checksum) = bytes.split_at(expected - CHECKSUM_SIZE);
+        if checksum(body) != stored_checksum {
+            return Err(DecodeError::ChecksumMismatch);
+        }
+
+        let mut rest = &body[HEADER_SIZE..];
+        let (packed_path, after_path) = rest.split_at((depth as usize).div_ceil(4));
+        rest = after_path;
+        let leaf_index =
+            QuadTreeIndex::unpack(depth, packed_path).ok_or(DecodeError::PathPadding)?;
+        let leaf_hash = read_hash(&mut rest);
+        let root_hash = read_hash(&mut rest);
+        let sibling_hashes = (0..depth)
+            .map(|_| {
+                [
+                    read_hash(&mut rest),
+                    read_hash(&mut rest),
+                    read_hash(&mut rest),
+                ]
+            })
+            .collect();
+
+        Ok(Self {
+            leaf_index,
+            leaf_hash,
+            sibling_hashes,
+            root_hash,
+        })
+    }
+}
+
+fn checksum(bytes: &[u8]) -> [u8; CHECKSUM_SIZE] {
+    let digest = Sha3_256::digest(bytes);
+    [digest[0], digest[1], digest[2], digest[3]]
+}
+
+fn read_hash(bytes: &mut &[u8]) -> [u8; 32] {
+    let (hash, rest) = bytes.split_at(32);
+    *bytes = rest;
+    hash.try_into().expect("split_at(32) yields 32 bytes")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{hash_leaf, SparseQuadTree};
+
+    fn proof(depth: u8) -> QuadTreeMembershipProof {
+        let mut tree = SparseQuadTree::new(depth);
+        let path: Vec<u8> = (0..depth).map(|level| level % 4).collect();
+        tree.insert(&path, hash_leaf(b"member"));
+        tree.insert(&[3; 32][..depth as usize], hash_leaf(b"neighbor"));
+        tree.generate_membership_proof(&path).unwrap()
+    }
+
+    #[test]
+    fn test_wire_format_round_trip() {
+        for depth in [1, 3, 5, 16] {
+            let proof = proof(depth);
+            let bytes = proof.to_bytes().unwrap();
+            assert_eq!(bytes.len(), proof.size_bytes());
+            assert_eq!(&bytes[..4], b"QTMP");
+
+            let decoded = QuadTreeMembershipProof::from_bytes(&bytes).unwrap();
+            assert_eq!(decoded.leaf_index, proof.leaf_index);
+            assert_eq!(decoded.leaf_hash, proof.leaf_hash);
+            assert_eq!(decoded.sibling_hashes, proof.sibling_hashes);
+            assert_eq!(decoded.root_hash, proof.root_hash);
+            assert!(decoded.verify());
+        }
+    }
+
+    #[test]
+    fn test_wire_format_rejects_corruption() {
+        let bytes = proof(3).to_bytes().unwrap();
+
+        let mut flipped = bytes.clone();
+        flipped[40] ^= 1;
+        assert_eq!(
+            QuadTreeMembershipProof::from_bytes(&flipped).unwrap_err(),
+            DecodeError::ChecksumMismatch
+        );
+
+        let mut wrong_magic = bytes.clone();
+        wrong_magic[0] = b'X';
+        assert_eq!(
+            QuadTreeMembershipProof::from_bytes(&wrong_magic).unwrap_err(),
+            DecodeError::BadMagic
+        );
+
+        let mut future = bytes.clone();
+        future[4] = 2;
+        assert_eq!(
+            QuadTreeMembershipProof::from_bytes(&future).unwrap_err(),
+            DecodeError::UnsupportedVersion(2)
+        );
+
+        assert_eq!(
+            QuadTreeMembershipProof::from_bytes(&bytes[..bytes.len() - 1]).unwrap_err(),
+            DecodeError::Length {
+                expected: bytes.len(),
+                actual: bytes.len() - 1
+            }
+        );
+    }
+
+    #[test]
+    fn test_wire_format_rejects_non_canonical_input() {
+        // A valid checksum over a bad header or path must still be rejected
+        let reseal = |mut bytes: Vec<u8>| {
+            let body_len = bytes.len() - CHECKSUM_SIZE;
+            let checksum = checksum(&bytes[..body_len]);
+            bytes[body_len..].copy_from_slice(&checksum);
+            bytes
+        };
+
+        let bytes = proof(3).to_bytes().unwrap();
+        let mut padded = bytes.clone();
+        padded[HEADER_SIZE] |= 0b01;
+        assert_eq!(
+            QuadTreeMembershipProof::from_bytes(&reseal(padded)).unwrap_err(),
+            DecodeError::PathPadding
+        );
+
+        let mut root_only = PROOF_MAGIC.to_vec();
+        root_only.extend_from_slice(&[PROOF_FORMAT_VERSION, 0]);
+        root_only.extend_from_slice(&[0u8; 64 + CHECKSUM_SIZE]);
+        assert_eq!(
+            QuadTreeMembershipProof::from_bytes(&reseal(root_only)).unwrap_err(),
+            DecodeError::ZeroDepth
+        );
+    }
+
+    #[test]
+    fn test_to_bytes_rejects_malformed_proof() {
+        let mut bad_digit = proof(3);
+        bad_digit.leaf_index.path[2] = 7;
+        assert_eq!(
+            bad_digit.to_bytes(),
+            Err(EncodeError::Index(IndexError::InvalidBranch {
+                level: 2,
+                branch: 7
+            }))
+        );
+
+        let mut missing_level = proof(3);
+        missing_level.sibling_hashes.pop();
+        assert_eq!(
+            missing_level.to_bytes(),
+            Err(EncodeError::SiblingCountMismatch {
+                depth: 3,
+                sibling_levels: 2
+            })
+        );
+    }
+}
@@ -0,0 +1,83 @@
+use core::fmt;
+
+/// Why a `QuadTreeIndex` is malformed
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum IndexError {
+    /// `path` does not have `depth` entries
+    DepthMismatch { depth: u8, path_len: usize },
+    /// A path digit outside 0-3 (`level` counts from the root)
+    InvalidBranch { level: usize, branch: u8 },
+    /// A child would be deeper than `u8::MAX`
+    TooDeep,
+}
+
+impl fmt::Display for IndexError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::DepthMismatch { depth, path_len } => write!(
+                f,
+                "Depth must match path length (depth {}, path length {})",
+                depth, path_len
+            ),
+            Self::InvalidBranch { level, branch } => write!(
+                f,
+                "Path indices must be 0-3 (got {} at level {})",
+                branch, level
+            ),
+            Self::TooDeep => write!(f, "Index depth cannot exceed 255"),
+        }
+    }
+}
+
+/// Why a membership proof failed to verify
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum VerifyError {
+    /// `leaf_index.path` does not have `leaf_index.depth` entries
+    PathLengthMismatch { depth: u8, path_len: usize },
+    /// The proof does not carry one sibling triple per level
+    SiblingCountMismatch { depth: u8, sibling_levels: usize },
+    /// A path digit outside 0-3 (`level` counts from the root)
+    InvalidBranch { level: usize, branch: u8 },
+    /// Every level hashed, but the result is not the claimed root
+    RootMismatch {
+        expected: [u8; 32],
+        computed: [u8; 32],
+    },
+}
+
+impl fmt::Display for VerifyError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::PathLengthMismatch { depth, path_len } => write!(
+                f,
+                "path has {} levels but the index depth is {}",
+                path_len, depth
+            ),
+            Self::SiblingCountMismatch {
+                depth,
+                sibling_levels,
+            } => write!(
+                f,
+                "proof has {} sibling levels for a depth {} leaf",
+                sibling_levels, depth
+            ),
+            Self::InvalidBranch { level, branch } => {
+                write!(f, "path digit {} at level {} is not 0-3", branch, level)
+            }
+            Self::RootMismatch { expected, computed } => {
+                write!(f, "computed root ")?;
+                write_hash_prefix(f, computed)?;
+                write!(f, "... does not match expected root ")?;
+                write_hash_prefix(f, expected)?;
+                write!(f, "...")
+            }
+        }
+    }
+}
+
+fn write_hash_prefix(f: &mut fmt::Formatter<'_>, hash: &[u8; 32]) -> fmt::Result {
+    for byte in &hash[..8] {
+        write!(f, "{:02x}", byte)?;
+    }
+    Ok(())
+}
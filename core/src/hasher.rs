@@ -0,0 +1,172 @@
+use sha3::{Digest, Keccak256, Sha3_256};
+
+use crate::HashScheme;
+
+/// Leaf and node hashing for a quaternary tree
+///
+/// Implementors supply the underlying 256-bit hash in `digest` and the
+/// domain separation scheme; the leaf/node layouts are shared so every
+/// hash function produces the same framing. Trees, proofs and `verify_with`
+/// are generic over this trait.
+pub trait QuadHasher: Copy {
+    /// Hash of the concatenation of `parts`
+    fn digest(&self, parts: &[&[u8]]) -> [u8; 32];
+
+    fn scheme(&self) -> HashScheme;
+
+    fn hash_leaf(&self, data: &[u8]) -> [u8; 32] {
+        match self.scheme() {
+            HashScheme::Legacy => self.digest(&[b"QUAD_LEAF:", data]),
+            HashScheme::V1 => self.digest(&[b"QUAD_V1_LEAF:", data]),
+        }
+    }
+
+    /// Hash four children into their parent
+    /// `height` is the parent's level above the leaves (1 = parent of leaves)
+    fn hash_node(
+        &self,
+        height: u8,
+        child0: &[u8; 32],
+        child1: &[u8; 32],
+        child2: &[u8; 32],
+        child3: &[u8; 32],
+    ) -> [u8; 32] {
+        match self.scheme() {
+            HashScheme::Legacy => self.digest(&[child0, child1, child2, child3]),
+            HashScheme::V1 => {
+                self.digest(&[b"QUAD_V1_NODE:", &[height], child0, child1, child2, child3])
+            }
+        }
+    }
+}
+
+/// SHA3-256 (the original tree hash)
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct Sha3Hasher(pub HashScheme);
+
+impl QuadHasher for Sha3Hasher {
+    fn digest(&self, parts: &[&[u8]]) -> [u8; 32] {
+        let mut hasher = Sha3_256::new();
+        for part in parts {
+            hasher.update(part);
+        }
+        hasher.finalize().into()
+    }
+
+    fn scheme(&self) -> HashScheme {
+        self.0
+    }
+}
+
+/// A bare `HashScheme` hashes with SHA3-256, same as `Sha3Hasher(scheme)`
+impl QuadHasher for HashScheme {
+    fn digest(&self, parts: &[&[u8]]) -> [u8; 32] {
+        Sha3Hasher(*self).digest(parts)
+    }
+
+    fn scheme(&self) -> HashScheme {
+        *self
+    }
+}
+
+/// Keccak-256 with the original padding, matching the EVM `keccak256` opcode
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct Keccak256Hasher(pub HashScheme);
+
+impl QuadHasher for Keccak256Hasher {
+    fn digest(&self, parts: &[&[u8]]) -> [u8; 32] {
+        let mut hasher = Keccak256::new();
+        for part in parts {
+            hasher.update(part);
+        }
+        hasher.finalize().into()
+    }
+
+    fn scheme(&self) -> HashScheme {
+        self.0
+    }
+}
+
+/// SHA-256
+#[cfg(feature = "sha256")]
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct Sha256Hasher(pub HashScheme);
+
+#[cfg(feature = "sha256")]
+impl QuadHasher for Sha256Hasher {
+    fn digest(&self, parts: &[&[u8]]) -> [u8; 32] {
+        let mut hasher = sha2::Sha256::new();
+        for part in parts {
+            hasher.update(part);
+        }
+        hasher.finalize().into()
+    }
+
+    fn scheme(&self) -> HashScheme {
+        self.0
+    }
+}
+
+/// BLAKE3 (256-bit output)
+#[cfg(feature = "blake3")]
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct Blake3Hasher(pub HashScheme);
+
+#[cfg(feature = "blake3")]
+impl QuadHasher for Blake3Hasher {
+    fn digest(&self, parts: &[&[u8]]) -> [u8; 32] {
+        let mut hasher = blake3::Hasher::new();
+        for part in parts {
+            hasher.update(part);
+        }
+        *hasher.finalize().as_bytes()
+    }
+
+    fn scheme(&self) -> HashScheme {
+        self.0
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{hash_leaf, hash_node, SparseQuadTree};
+
+    #[test]
+    fn test_sha3_hasher_matches_legacy_functions() {
+        let hasher = Sha3Hasher::default();
+        let children = [[1u8; 32], [2u8; 32], [3u8; 32], [4u8; 32]];
+
+        assert_eq!(hasher.hash_leaf(b"data"), hash_leaf(b"data"));
+        assert_eq!(
+            hasher.hash_node(1, &children[0], &children[1], &children[2], &children[3]),
+            hash_node(&children[0], &children[1], &children[2], &children[3])
+        );
+    }
+
+    #[test]
+    fn test_keccak_empty_digest() {
+        // keccak256("") as returned by the EVM
+        let expected = [
+            0xc5, 0xd2, 0x46, 0x01, 0x86, 0xf7, 0x23, 0x3c, 0x92, 0x7e, 0x7d, 0xb2, 0xdc, 0xc7,
+            0x03, 0xc0, 0xe5, 0x00, 0xb6, 0x53, 0xca, 0x82, 0x27, 0x3b, 0x7b, 0xfa, 0xd8, 0x04,
+            0x5d, 0x85, 0xa4, 0x70,
+        ];
+        assert_eq!(Keccak256Hasher::default().digest(&[]), expected);
+    }
+
+    #[test]
+    fn test_tree_generic_over_hasher() {
+        let path = [2u8, 0, 1];
+        let mut sha3 = SparseQuadTree::new(3);
+        let mut keccak = SparseQuadTree::with_hasher(3, Keccak256Hasher::default());
+        sha3.insert(&path, hash_leaf(b"member"));
+        keccak.insert(&path, Keccak256Hasher::default().hash_leaf(b"member"));
+
+        assert_ne!(sha3.root(), keccak.root());
+
+        let proof = keccak.generate_membership_proof(&path).unwrap();
+        assert!(proof.verify_with(Keccak256Hasher::default()));
+        assert!(!proof.verify());
+    }
+}
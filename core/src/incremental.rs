@@ -0,0 +1,350 @@
+use alloc::boxed::Box;
+use alloc::vec::Vec;
+use core::cmp::Ordering;
+
+use crate::{default_hashes_with, HashScheme, QuadHasher, QuadTreeIndex, QuadTreeMembershipProof};
+
+/// Append-only quaternary Merkle tree (4-ary analogue of the Zcash/Tornado
+/// incremental Merkle tree)
+///
+/// Leaves are appended left to right. Only the frontier is kept: for every
+/// level, the completed nodes of the rightmost group that has not yet been
+/// folded into its parent. Unfilled positions hash as empty subtrees, so the
+/// root matches a `SparseQuadTree` holding the same leaves.
+#[derive(Debug, Clone)]
+pub struct IncrementalQuadTree<H: QuadHasher = HashScheme> {
+    depth: u8,
+    hasher: H,
+    size: u64,
+    /// Pending completed nodes per level (0 = leaves). Level 0 may hold a full
+    /// group of 4 until the next append folds it upward; every other level
+    /// holds at most 3.
+    frontier: Vec<Vec<[u8; 32]>>,
+    /// Empty-subtree hashes indexed by height above the leaves
+    defaults: Vec<[u8; 32]>,
+}
+
+impl IncrementalQuadTree {
+    /// Create an empty tree with 4^depth leaf positions (legacy SHA3-256 hashing)
+    pub fn new(depth: u8) -> Self {
+        Self::with_hasher(depth, HashScheme::Legacy)
+    }
+}
+
+impl<H: QuadHasher> IncrementalQuadTree<H> {
+    /// Create an empty tree that hashes nodes with the given hasher
+    pub fn with_hasher(depth: u8, hasher: H) -> Self {
+        assert!(depth >= 1, "Tree must have at least one level");
+        assert!(depth <= 31, "Leaf positions must fit in a u64");
+        Self {
+            depth,
+            hasher,
+            size: 0,
+            frontier: (0..depth).map(|_| Vec::with_capacity(4)).collect(),
+            defaults: default_hashes_with(depth, hasher),
+        }
+    }
+
+    pub fn depth(&self) -> u8 {
+        self.depth
+    }
+
+    /// Number of appended leaves
+    pub fn len(&self) -> u64 {
+        self.size
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.size == 0
+    }
+
+    pub fn capacity(&self) -> u64 {
+        4u64.pow(self.depth as u32)
+    }
+
+    /// Append a leaf hash, returning its index
+    /// Returns `None` if the tree is full
+    pub fn append(&mut self, leaf_hash: [u8; 32]) -> Option<QuadTreeIndex> {
+        if self.size == self.capacity() {
+            return None;
+        }
+
+        // Fold groups completed by the previous append into their parents
+        for level in 0..self.depth as usize - 1 {
+            if self.frontier[level].len() < 4 {
+                break;
+            }
+            let group = &self.frontier[level];
+            let height = level as u8 + 1;
+            let parent = self
+                .hasher
+                .hash_node(height, &group[0], &group[1], &group[2], &group[3]);
+            self.frontier[level].clear();
+            self.frontier[level + 1].push(parent);
+        }
+
+        self.frontier[0].push(leaf_hash);
+        self.size += 1;
+
+        Some(QuadTreeIndex::from_leaf_number(self.size - 1, self.depth))
+    }
+
+    pub fn root(&self) -> [u8; 32] {
+        let mut carry: Option<[u8; 32]> = None;
+
+        for level in 0..self.depth as usize {
+            let mut children = [self.defaults[level]; 4];
+            for (child, hash) in children
+                .iter_mut()
+                .zip(self.frontier[level].iter().chain(carry.iter()))
+            {
+                *child = *hash;
+            }
+
+            carry = Some(self.hasher.hash_node(
+                level as u8 + 1,
+                &children[0],
+                &children[1],
+                &children[2],
+                &children[3],
+            ));
+        }
+
+        carry.unwrap_or(self.defaults[self.depth as usize])
+    }
+
+    /// Start a witness for the most recently appended leaf
+    /// Returns `None` if the tree is empty
+    pub fn witness(&self) -> Option<QuadTreeWitness<H>> {
+        let leaf_hash = *self.frontier[0].last()?;
+        let leaf_index = QuadTreeIndex::from_leaf_number(self.size - 1, self.depth);
+
+        // The frontier holds exactly the left siblings of the latest leaf's path
+        let mut left_siblings: Vec<Vec<[u8; 32]>> = self.frontier.clone();
+        left_siblings[0].pop();
+
+        Some(QuadTreeWitness {
+            leaf_index,
+            leaf_hash,
+            left_siblings,
+            right_filled: Vec::new(),
+            cursor: None,
+            hasher: self.hasher,
+            defaults: self.defaults.clone(),
+        })
+    }
+}
+
+/// Authentication path for one leaf of an `IncrementalQuadTree`
+///
+/// Left siblings are fixed when the witness is created. Right siblings fill
+/// in as later leaves are appended, so every leaf appended to the tree must
+/// also be passed to `QuadTreeWitness::append` to keep the witness current.
+#[derive(Debug, Clone)]
+pub struct QuadTreeWitness<H: QuadHasher = HashScheme> {
+    leaf_index: QuadTreeIndex,
+    leaf_hash: [u8; 32],
+    /// Completed siblings to the left of the path, per level (0 = leaves)
+    left_siblings: Vec<Vec<[u8; 32]>>,
+    /// Completed right siblings, in the order they are filled
+    right_filled: Vec<[u8; 32]>,
+    /// Partially filled right sibling subtree, if any
+    cursor: Option<Box<IncrementalQuadTree<H>>>,
+    hasher: H,
+    defaults: Vec<[u8; 32]>,
+}
+
+impl<H: QuadHasher> QuadTreeWitness<H> {
+    pub fn leaf_index(&self) -> &QuadTreeIndex {
+        &self.leaf_index
+    }
+
+    /// Record a leaf appended to the tree after this witness was created
+    /// Returns `false` if no positions remain to the right of the witnessed leaf
+    pub fn append(&mut self, leaf_hash: [u8; 32]) -> bool {
+        let level = match self.next_right_level() {
+            Some(level) => level,
+            None => return false,
+        };
+
+        if level == 0 {
+            self.right_filled.push(leaf_hash);
+            return true;
+        }
+
+        let hasher = self.hasher;
+        let cursor = self
+            .cursor
+            .get_or_insert_with(|| Box::new(IncrementalQuadTree::with_hasher(level, hasher)));
+        cursor.append(leaf_hash);
+
+        if cursor.len() == cursor.capacity() {
+            self.right_filled.push(cursor.root());
+            self.cursor = None;
+        }
+
+        true
+    }
+
+    /// Build a standard membership proof against the current tree root
+    pub fn membership_proof(&self) -> QuadTreeMembershipProof {
+        let depth = self.leaf_index.depth as usize;
+        let mut sibling_hashes = Vec::with_capacity(depth);
+        let mut current_hash = self.leaf_hash;
+        let mut right_idx = 0;
+
+        for level in 0..depth {
+            let branch = self.leaf_index.path[depth - 1 - level] as usize;
+            let mut children = [self.defaults[level]; 4];
+
+            children[..branch].copy_from_slice(&self.left_siblings[level]);
+            children[branch] = current_hash;
+
+            for child in children.iter_mut().skip(branch + 1) {
+                match right_idx.cmp(&self.right_filled.len()) {
+                    Ordering::Less => *child = self.right_filled[right_idx],
+                    Ordering::Equal => {
+                        if let Some(cursor) = &self.cursor {
+                            *child = cursor.root();
+                        }
+                    }
+                    Ordering::Greater => {}
+                }
+                right_idx += 1;
+            }
+
+            let mut siblings = [[0u8; 32]; 3];
+            let mut sibling_idx = 0;
+            for (i, child) in children.iter().enumerate() {
+                if i != branch {
+                    siblings[sibling_idx] = *child;
+                    sibling_idx += 1;
+                }
+            }
+            sibling_hashes.push(siblings);
+
+            current_hash = self.hasher.hash_node(
+                level as u8 + 1,
+                &children[0],
+                &children[1],
+                &children[2],
+                &children[3],
+            );
+        }
+
+        QuadTreeMembershipProof {
+            leaf_index: self.leaf_index.clone(),
+            leaf_hash: self.leaf_hash,
+            sibling_hashes,
+            root_hash: current_hash,
+        }
+    }
+
+    /// Level of the first right sibling that is not yet complete
+    fn next_right_level(&self) -> Option<u8> {
+        let depth = self.leaf_index.depth as usize;
+        let mut remaining = self.right_filled.len();
+
+        for level in 0..depth {
+            let branch = self.leaf_index.path[depth - 1 - level] as usize;
+            let right_slots = 3 - branch;
+            if remaining < right_slots {
+                return Some(level as u8);
+            }
+            remaining -= right_slots;
+        }
+
+        None
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{hash_leaf, SparseQuadTree};
+
+    #[test]
+    fn test_root_matches_sparse_tree() {
+        let mut tree = IncrementalQuadTree::new(3);
+        let mut sparse = SparseQuadTree::new(3);
+        assert_eq!(tree.root(), sparse.root());
+
+        for i in 0..64u32 {
+            let leaf = hash_leaf(format!("leaf{}", i).as_bytes());
+            let index = tree.append(leaf).unwrap();
+            sparse.insert(&index.path, leaf);
+            assert_eq!(
+                tree.root(),
+                sparse.root(),
+                "Root mismatch after {} leaves",
+                i + 1
+            );
+        }
+
+        assert!(tree.append(hash_leaf(b"overflow")).is_none());
+    }
+
+    #[test]
+    fn test_witness_tracks_later_appends() {
+        let mut tree = IncrementalQuadTree::new(3);
+        let mut witnesses: Vec<QuadTreeWitness> = Vec::new();
+
+        for i in 0..40u32 {
+            let leaf = hash_leaf(format!("leaf{}", i).as_bytes());
+            tree.append(leaf);
+            for witness in witnesses.iter_mut() {
+                assert!(witness.append(leaf));
+            }
+            if i % 7 == 3 {
+                witnesses.push(tree.witness().unwrap());
+            }
+        }
+
+        for witness in &witnesses {
+            let proof = witness.membership_proof();
+            assert_eq!(proof.root_hash, tree.root());
+            assert!(
+                proof.verify(),
+                "Proof for {:?} should verify",
+                witness.leaf_index()
+            );
+        }
+    }
+
+    #[test]
+    fn test_v1_scheme_witness() {
+        let mut tree = IncrementalQuadTree::with_hasher(2, HashScheme::V1);
+        let mut sparse = SparseQuadTree::with_hasher(2, HashScheme::V1);
+        tree.append(hash_leaf(b"first"));
+        let mut witness = tree.witness().unwrap();
+
+        for i in 0..6u32 {
+            let leaf = hash_leaf(format!("leaf{}", i).as_bytes());
+            let index = tree.append(leaf).unwrap();
+            sparse.insert(&index.path, leaf);
+            witness.append(leaf);
+        }
+        sparse.insert(&[0, 0], hash_leaf(b"first"));
+
+        let proof = witness.membership_proof();
+        assert_eq!(tree.root(), sparse.root());
+        assert_eq!(proof.root_hash, tree.root());
+        assert!(proof.verify_with(HashScheme::V1));
+    }
+
+    #[test]
+    fn test_witness_for_latest_leaf() {
+        let mut tree = IncrementalQuadTree::new(2);
+        assert!(tree.witness().is_none());
+
+        for i in 0..16u32 {
+            let index = tree
+                .append(hash_leaf(format!("leaf{}", i).as_bytes()))
+                .unwrap();
+            let proof = tree.witness().unwrap().membership_proof();
+            assert_eq!(proof.leaf_index, index);
+            assert_eq!(proof.root_hash, tree.root());
+            assert!(proof.verify());
+        }
+    }
+}
@@ -0,0 +1,144 @@
+use alloc::vec::Vec;
+use serde::{Deserialize, Serialize};
+
+use crate::{reconstruct_root, QuadHasher, QuadTreeIndex, QuadTreeMembershipProof, Sha3Hasher};
+
+/// Size of an encoded ML-KEM-768 encapsulation key (FIPS 203: 384k + 32, k = 3)
+pub const ML_KEM_768_ENCAPSULATION_KEY_SIZE: usize = 1184;
+
+/// ML-KEM modulus q
+const ML_KEM_Q: u16 = 3329;
+
+/// Check that `key` is a well-formed ML-KEM-768 encapsulation key
+///
+/// FIPS 203 input validation: the key is 1184 bytes, and the 768
+/// 12-bit coefficients of t (the first 1152 bytes) are all reduced mod q.
+/// The trailing 32-byte seed rho is unconstrained.
+pub fn is_well_formed_ml_kem_768_key(key: &[u8]) -> bool {
+    if key.len() != ML_KEM_768_ENCAPSULATION_KEY_SIZE {
+        return false;
+    }
+
+    key[..ML_KEM_768_ENCAPSULATION_KEY_SIZE - 32]
+        .chunks_exact(3)
+        .all(|bytes| {
+            let c0 = bytes[0] as u16 | ((bytes[1] as u16 & 0x0F) << 8);
+            let c1 = (bytes[1] as u16 >> 4) | ((bytes[2] as u16) << 4);
+            c0 < ML_KEM_Q && c1 < ML_KEM_Q
+        })
+}
+
+/// Membership proof that carries the leaf's ML-KEM-768 encapsulation key
+/// instead of its hash
+///
+/// The verifier recomputes `hash_leaf` over the key, so a valid proof shows
+/// the prover knows a well-formed key whose hash is in the tree, not just a
+/// 32-byte value that happens to reconstruct the root.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct QuadTreeKeyMembershipProof {
+    pub leaf_index: QuadTreeIndex,
+    /// Private witness: the 1184-byte encapsulation key stored in the leaf
+    pub encapsulation_key: Vec<u8>,
+    /// Same layout as `QuadTreeMembershipProof::sibling_hashes` (LEAF to ROOT)
+    pub sibling_hashes: Vec<[[u8; 32]; 3]>,
+    pub root_hash: [u8; 32],
+}
+
+impl QuadTreeKeyMembershipProof {
+    /// Attach the encapsulation key to an existing membership proof
+    /// Returns `None` if the key does not hash to the proof's leaf under `hasher`
+    pub fn from_membership<H: QuadHasher>(
+        proof: QuadTreeMembershipProof,
+        encapsulation_key: Vec<u8>,
+        hasher: H,
+    ) -> Option<Self> {
+        if hasher.hash_leaf(&encapsulation_key) != proof.leaf_hash {
+            return None;
+        }
+
+        Some(Self {
+            leaf_index: proof.leaf_index,
+            encapsulation_key,
+            sibling_hashes: proof.sibling_hashes,
+            root_hash: proof.root_hash,
+        })
+    }
+
+    /// Check the key encoding, hash it into a leaf, then walk the path to the root
+    pub fn verify(&self) -> bool {
+        self.verify_with(Sha3Hasher::default())
+    }
+
+    /// Verify against a tree built with the given hasher
+    pub fn verify_with<H: QuadHasher>(&self, hasher: H) -> bool {
+        if !is_well_formed_ml_kem_768_key(&self.encapsulation_key) {
+            return false;
+        }
+
+        let leaf_hash = hasher.hash_leaf(&self.encapsulation_key);
+        match reconstruct_root(hasher, &self.leaf_index, &leaf_hash, &self.sibling_hashes) {
+            Ok(root) => root == self.root_hash,
+            Err(_) => false,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{hash_leaf, hash_node, HashScheme};
+
+    /// Well-formed key with every coefficient set to `coefficient`
+    fn encapsulation_key(coefficient: u16, rho: u8) -> Vec<u8> {
+        let mut key = Vec::with_capacity(ML_KEM_768_ENCAPSULATION_KEY_SIZE);
+        for _ in 0..384 {
+            key.push(coefficient as u8);
+            key.push(((coefficient >> 8) as u8) | ((coefficient as u8 & 0x0F) << 4));
+            key.push((coefficient >> 4) as u8);
+        }
+        key.extend_from_slice(&[rho; 32]);
+        key
+    }
+
+    fn key_proof(key: Vec<u8>) -> QuadTreeKeyMembershipProof {
+        let leaves = [
+            hash_leaf(&encapsulation_key(1, 0)),
+            hash_leaf(&key),
+            hash_leaf(&encapsulation_key(2, 0)),
+            hash_leaf(&encapsulation_key(3, 0)),
+        ];
+        let membership = QuadTreeMembershipProof {
+            leaf_index: QuadTreeIndex::new(1, vec![1]),
+            leaf_hash: leaves[1],
+            sibling_hashes: vec![[leaves[0], leaves[2], leaves[3]]],
+            root_hash: hash_node(&leaves[0], &leaves[1], &leaves[2], &leaves[3]),
+        };
+        QuadTreeKeyMembershipProof::from_membership(membership, key, HashScheme::Legacy).unwrap()
+    }
+
+    #[test]
+    fn test_well_formed_key_check() {
+        assert!(is_well_formed_ml_kem_768_key(&encapsulation_key(
+            3328, 0xFF
+        )));
+        assert!(!is_well_formed_ml_kem_768_key(&encapsulation_key(3329, 0)));
+        assert!(!is_well_formed_ml_kem_768_key(&[0u8; 1183]));
+    }
+
+    #[test]
+    fn test_key_proof_verifies() {
+        let proof = key_proof(encapsulation_key(1234, 7));
+        assert!(proof.verify());
+
+        let mut wrong_key = proof.clone();
+        wrong_key.encapsulation_key[1183] ^= 1;
+        assert!(!wrong_key.verify(), "Key must hash to the proven leaf");
+    }
+
+    #[test]
+    fn test_key_proof_rejects_malformed_key() {
+        // Hashes correctly into the tree but is not a valid ML-KEM-768 key
+        let proof = key_proof(encapsulation_key(4000, 7));
+        assert!(!proof.verify());
+    }
+}
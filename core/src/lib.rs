@@ -9,8 +9,46 @@ use alloc::format;
 use serde::{Deserialize, Serialize};
 use sha3::{Digest, Sha3_256};
 
+pub mod encoding;
+pub mod error;
+pub mod hasher;
+pub mod incremental;
+pub mod key_proof;
+pub mod multiproof;
+pub mod nullifier;
+#[cfg(feature = "poseidon2")]
+pub mod poseidon2;
+mod serde_hex;
+pub mod sparse;
+pub mod streaming;
+
+pub use encoding::{
+    encoded_proof_size, DecodeError, EncodeError, PROOF_FORMAT_VERSION, PROOF_MAGIC,
+};
+pub use error::{IndexError, VerifyError};
+#[cfg(feature = "blake3")]
+pub use hasher::Blake3Hasher;
+#[cfg(feature = "sha256")]
+pub use hasher::Sha256Hasher;
+pub use hasher::{Keccak256Hasher, QuadHasher, Sha3Hasher};
+pub use incremental::{IncrementalQuadTree, QuadTreeWitness};
+pub use key_proof::{
+    is_well_formed_ml_kem_768_key, QuadTreeKeyMembershipProof, ML_KEM_768_ENCAPSULATION_KEY_SIZE,
+};
+pub use multiproof::QuadTreeMultiProof;
+pub use nullifier::{
+    hash_nullifier, identity_commitment, identity_commitment_with, identity_leaf,
+    QuadTreeNullifierProof,
+};
+#[cfg(feature = "poseidon2")]
+pub use poseidon2::{Poseidon2Hasher, Poseidon2Permutation};
+pub use sparse::{default_hashes, default_hashes_with, depth_for_leaf_count, SparseQuadTree};
+pub use streaming::{LevelSink, StreamingRootBuilder};
+
 /// Quaternary tree index representing position in tree
-#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+/// Deserialization runs the same checks as `try_new`
+#[derive(Debug, Clone, PartialEq, Eq, PartialOrd, Ord, Serialize, Deserialize)]
+#[serde(try_from = "UncheckedQuadTreeIndex")]
 pub struct QuadTreeIndex {
     /// Depth in tree (0 = root, 5 = leaf for 1024 leaves)
     pub depth: u8,
@@ -18,11 +56,34 @@ pub struct QuadTreeIndex {
     pub path: Vec<u8>,
 }
 
+/// Wire form of `QuadTreeIndex` before validation
+#[derive(Deserialize)]
+struct UncheckedQuadTreeIndex {
+    depth: u8,
+    path: Vec<u8>,
+}
+
+impl TryFrom<UncheckedQuadTreeIndex> for QuadTreeIndex {
+    type Error = IndexError;
+
+    fn try_from(index: UncheckedQuadTreeIndex) -> Result<Self, IndexError> {
+        Self::try_new(index.depth, index.path)
+    }
+}
+
 impl QuadTreeIndex {
+    /// Panics on a malformed index; see `try_new`
     pub fn new(depth: u8, path: Vec<u8>) -> Self {
-        assert_eq!(depth as usize, path.len(), "Depth must match path length");
-        assert!(path.iter().all(|&x| x < 4), "Path indices must be 0-3");
-        Self { depth, path }
+        match Self::try_new(depth, path) {
+            Ok(index) => index,
+            Err(err) => panic!("{}", err),
+        }
+    }
+
+    /// Checks that `path` has `depth` digits, each 0-3
+    pub fn try_new(depth: u8, path: Vec<u8>) -> Result<Self, IndexError> {
+        check_path(depth, &path)?;
+        Ok(Self { depth, path })
     }
 
     pub fn root() -> Self {
@@ -32,14 +93,148 @@ impl QuadTreeIndex {
         }
     }
 
+    /// Panics on a branch outside 0-3; see `try_child`
     pub fn child(&self, branch: u8) -> Self {
-        assert!(branch < 4, "Branch index must be 0-3");
+        match self.try_child(branch) {
+            Ok(child) => child,
+            Err(err) => panic!("{}", err),
+        }
+    }
+
+    /// Index of child `branch` (0-3) one level below
+    pub fn try_child(&self, branch: u8) -> Result<Self, IndexError> {
+        if branch >= 4 {
+            return Err(IndexError::InvalidBranch {
+                level: self.path.len(),
+                branch,
+            });
+        }
+        let depth = self.depth.checked_add(1).ok_or(IndexError::TooDeep)?;
         let mut path = self.path.clone();
         path.push(branch);
+        Ok(Self { depth, path })
+    }
+
+    /// Leaf slot for a key in a tree of the given depth
+    /// Each level consumes the next 2 bits of `key_hash`, most significant first
+    pub fn for_key(key_hash: &[u8; 32], depth: u8) -> Self {
+        assert!(depth <= 128, "Key hash only provides 128 levels");
         Self {
-            depth: self.depth + 1,
-            path,
+            depth,
+            path: read_packed_path(depth, key_hash),
+        }
+    }
+
+    /// Index of the `number`th node (left to right) at `depth`
+    /// The path is the base-4 digits of `number`, most significant first
+    pub fn from_leaf_number(number: u64, depth: u8) -> Self {
+        assert!(depth <= 32, "Leaf numbers only cover 32 levels");
+        assert!(
+            depth == 32 || number < 1u64 << (2 * depth as u32),
+            "Leaf number out of range for depth"
+        );
+        let path = (0..depth)
+            .rev()
+            .map(|level| ((number >> (2 * level as u32)) & 0b11) as u8)
+            .collect();
+        Self { depth, path }
+    }
+
+    /// Position of this node among the nodes at its depth (inverse of `from_leaf_number`)
+    pub fn leaf_number(&self) -> u64 {
+        assert!(self.depth <= 32, "Leaf numbers only cover 32 levels");
+        self.path
+            .iter()
+            .fold(0, |number, &branch| (number << 2) | branch as u64)
+    }
+
+    /// Every node at `depth`, left to right
+    pub fn leaves(depth: u8) -> impl Iterator<Item = Self> {
+        assert!(depth <= 32, "Leaf numbers only cover 32 levels");
+        let last = match depth {
+            0 => 0,
+            _ => u64::MAX >> (64 - 2 * depth as u32),
+        };
+        (0..=last).map(move |number| Self::from_leaf_number(number, depth))
+    }
+
+    /// Node directly above this one, or `None` for the root
+    pub fn parent(&self) -> Option<Self> {
+        self.ancestor(self.depth.checked_sub(1)?)
+    }
+
+    /// Node on this node's path at `depth`, or `None` if `depth` is below it
+    pub fn ancestor(&self, depth: u8) -> Option<Self> {
+        if depth > self.depth {
+            return None;
+        }
+        Some(Self {
+            depth,
+            path: self.path[..depth as usize].to_vec(),
+        })
+    }
+
+    /// Next node to the left at the same depth, or `None` at the left edge
+    pub fn left_neighbor(&self) -> Option<Self> {
+        let mut path = self.path.clone();
+        // Borrow: trailing 0s become 3s, the first non-zero digit decrements
+        for branch in path.iter_mut().rev() {
+            if *branch > 0 {
+                *branch -= 1;
+                return Some(Self {
+                    depth: self.depth,
+                    path,
+                });
+            }
+            *branch = 3;
+        }
+        None
+    }
+
+    /// Next node to the right at the same depth, or `None` at the right edge
+    pub fn right_neighbor(&self) -> Option<Self> {
+        let mut path = self.path.clone();
+        // Carry: trailing 3s become 0s, the first digit below 3 increments
+        for branch in path.iter_mut().rev() {
+            if *branch < 3 {
+                *branch += 1;
+                return Some(Self {
+                    depth: self.depth,
+                    path,
+                });
+            }
+            *branch = 0;
+        }
+        None
+    }
+
+    /// Path packed 2 bits per level, most significant bits first,
+    /// into `depth.div_ceil(4)` bytes (unused low bits are zero)
+    /// Fails if the public fields were set to an index `try_new` rejects
+    pub fn pack(&self) -> Result<Vec<u8>, IndexError> {
+        check_path(self.depth, &self.path)?;
+        let mut packed = vec![0u8; (self.depth as usize).div_ceil(4)];
+        for (level, &branch) in self.path.iter().enumerate() {
+            packed[level / 4] |= branch << (6 - 2 * (level % 4));
         }
+        Ok(packed)
+    }
+
+    /// Read `depth` levels packed by `pack`
+    /// Returns `None` unless `packed` is exactly `depth.div_ceil(4)` bytes with
+    /// zero unused low bits, so each index has a single packed form
+    pub fn unpack(depth: u8, packed: &[u8]) -> Option<Self> {
+        if packed.len() != (depth as usize).div_ceil(4) {
+            return None;
+        }
+        let used_bits = 2 * (depth as u32 % 4);
+        if used_bits != 0 && packed[packed.len() - 1] & (0xff >> used_bits) != 0 {
+            return None;
+        }
+        Some(Self {
+            depth,
+            path: read_packed_path(depth, packed),
+        })
     }
 
     pub fn branch_at_depth(&self, depth: usize) -> Option<u8> {
@@ -51,6 +246,30 @@ impl QuadTreeIndex {
     }
 }
 
+/// The first `depth` 2-bit digits of `packed`, most significant first
+fn read_packed_path(depth: u8, packed: &[u8]) -> Vec<u8> {
+    (0..depth as usize)
+        .map(|level| (packed[level / 4] >> (6 - 2 * (level % 4))) & 0b11)
+        .collect()
+}
+
+/// `path` has `depth` digits, each 0-3
+fn check_path(depth: u8, path: &[u8]) -> Result<(), IndexError> {
+    if depth as usize != path.len() {
+        return Err(IndexError::DepthMismatch {
+            depth,
+            path_len: path.len(),
+        });
+    }
+    if let Some(level) = path.iter().position(|&branch| branch >= 4) {
+        return Err(IndexError::InvalidBranch {
+            level,
+            branch: path[level],
+        });
+    }
+    Ok(())
+}
+
 /// Hash combining function for quaternary Merkle tree
 pub fn hash_node(
     child0: &[u8; 32],
@@ -74,15 +293,123 @@ pub fn hash_leaf(data: &[u8]) -> [u8; 32] {
     hasher.finalize().into()
 }
 
+/// Domain separation scheme for leaf and internal node hashes
+///
+/// `Legacy` is the original layout: leaves are tagged, internal nodes hash
+/// the bare 128 bytes of their children, and nothing binds the level.
+/// `V1` tags both leaves and nodes with a versioned prefix and binds each
+/// node to its height above the leaves, so a leaf can never be confused
+/// with an internal node and a subtree cannot be replayed at another level.
+///
+/// The scheme is a property of the tree and is not carried in proofs, so a
+/// prover cannot pick a weaker scheme than the verifier expects.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub enum HashScheme {
+    #[default]
+    Legacy,
+    V1,
+}
+
+impl HashScheme {
+    /// `hash_leaf` under this scheme with SHA3-256 (see `QuadHasher` for others)
+    pub fn hash_leaf(self, data: &[u8]) -> [u8; 32] {
+        Sha3Hasher(self).hash_leaf(data)
+    }
+
+    /// Hash four children into their parent with SHA3-256
+    /// `height` is the parent's level above the leaves (1 = parent of leaves)
+    pub fn hash_node(
+        self,
+        height: u8,
+        child0: &[u8; 32],
+        child1: &[u8; 32],
+        child2: &[u8; 32],
+        child3: &[u8; 32],
+    ) -> [u8; 32] {
+        Sha3Hasher(self).hash_node(height, child0, child1, child2, child3)
+    }
+}
+
+/// Hash stored in leaf slots that hold no value (sparse trees, padding)
+pub const EMPTY_LEAF_HASH: [u8; 32] = [0u8; 32];
+
+/// Reconstruct the root hash from a leaf hash and its LEAF-to-ROOT siblings
+/// Fails if the index and sibling levels are inconsistent
+pub(crate) fn reconstruct_root<H: QuadHasher>(
+    hasher: H,
+    leaf_index: &QuadTreeIndex,
+    leaf_hash: &[u8; 32],
+    sibling_hashes: &[[[u8; 32]; 3]],
+) -> Result<[u8; 32], VerifyError> {
+    let depth = leaf_index.depth;
+    if depth as usize != leaf_index.path.len() {
+        return Err(VerifyError::PathLengthMismatch {
+            depth,
+            path_len: leaf_index.path.len(),
+        });
+    }
+    if depth as usize != sibling_hashes.len() {
+        return Err(VerifyError::SiblingCountMismatch {
+            depth,
+            sibling_levels: sibling_hashes.len(),
+        });
+    }
+
+    let mut current_hash = *leaf_hash;
+
+    // Iterate through sibling levels from LEAF to ROOT
+    for (level_from_leaf, siblings) in sibling_hashes.iter().enumerate() {
+        let path_level = depth as usize - 1 - level_from_leaf;
+        let branch = leaf_index.path[path_level];
+
+        if branch >= 4 {
+            return Err(VerifyError::InvalidBranch {
+                level: path_level,
+                branch,
+            });
+        }
+        let branch_index = branch as usize;
+
+        // Reconstruct the 4 children
+        let mut children = [[0u8; 32]; 4];
+        let mut sibling_idx = 0;
+
+        for (i, child) in children.iter_mut().enumerate() {
+            if i == branch_index {
+                *child = current_hash;
+            } else {
+                *child = siblings[sibling_idx];
+                sibling_idx += 1;
+            }
+        }
+
+        current_hash = hasher.hash_node(
+            level_from_leaf as u8 + 1,
+            &children[0],
+            &children[1],
+            &children[2],
+            &children[3],
+        );
+    }
+
+    Ok(current_hash)
+}
+
 /// Merkle membership proof for quaternary tree
+/// JSON renders hashes as hex and the path as a digit string (see `serde_hex`);
+/// bincode keeps the plain layout the guest reads
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct QuadTreeMembershipProof {
+    #[serde(with = "serde_hex::path")]
     pub leaf_index: QuadTreeIndex,
+    #[serde(with = "serde_hex::hash")]
     pub leaf_hash: [u8; 32],
     /// For each level, contains the 3 sibling hashes in ascending position order
     /// (excluding the branch we take)
     /// Stored from LEAF to ROOT (reverse of path traversal)
+    #[serde(with = "serde_hex::sibling_triples")]
     pub sibling_hashes: Vec<[[u8; 32]; 3]>,
+    #[serde(with = "serde_hex::hash")]
     pub root_hash: [u8; 32],
 }
 
@@ -90,45 +417,96 @@ impl QuadTreeMembershipProof {
     /// Verify the proof by reconstructing the root hash
     /// We start at the leaf and work our way UP to the root
     pub fn verify(&self) -> bool {
-        if self.leaf_index.depth as usize != self.sibling_hashes.len() {
-            return false;
+        self.verify_with(Sha3Hasher::default())
+    }
+
+    /// Verify against a tree built with the given hasher
+    /// (a bare `HashScheme` selects SHA3-256 with that scheme)
+    pub fn verify_with<H: QuadHasher>(&self, hasher: H) -> bool {
+        self.verify_detailed_with(hasher).is_ok()
+    }
+
+    /// Verify against a root the caller already trusts (e.g. a published registry root)
+    /// `self.root_hash` comes from the same untrusted proof and is ignored
+    pub fn verify_against(&self, expected_root: &[u8; 32]) -> bool {
+        self.verify_detailed_against(expected_root, Sha3Hasher::default())
+            .is_ok()
+    }
+
+    /// `verify_against` for any hasher, reporting which check failed
+    pub fn verify_detailed_against<H: QuadHasher>(
+        &self,
+        expected_root: &[u8; 32],
+        hasher: H,
+    ) -> Result<(), VerifyError> {
+        let computed = reconstruct_root(
+            hasher,
+            &self.leaf_index,
+            &self.leaf_hash,
+            &self.sibling_hashes,
+        )?;
+        if computed != *expected_root {
+            return Err(VerifyError::RootMismatch {
+                expected: *expected_root,
+                computed,
+            });
         }
+        Ok(())
+    }
 
-        let mut current_hash = self.leaf_hash;
+    /// `verify`, reporting which check failed
+    pub fn verify_detailed(&self) -> Result<(), VerifyError> {
+        self.verify_detailed_with(Sha3Hasher::default())
+    }
 
-        // Iterate through sibling levels from LEAF to ROOT
-        for (level_from_leaf, siblings) in self.sibling_hashes.iter().enumerate() {
-            let path_level = self.leaf_index.depth as usize - 1 - level_from_leaf;
+    /// `verify_with`, reporting which check failed
+    pub fn verify_detailed_with<H: QuadHasher>(&self, hasher: H) -> Result<(), VerifyError> {
+        self.verify_detailed_against(&self.root_hash, hasher)
+    }
 
-            let branch_index = match self.leaf_index.branch_at_depth(path_level) {
-                Some(idx) => idx as usize,
-                None => return false,
-            };
+    /// Length of `to_bytes()`
+    pub fn size_bytes(&self) -> usize {
+        encoded_proof_size(self.leaf_index.depth)
+    }
+}
 
-            if branch_index >= 4 {
-                return false;
-            }
+/// Merkle non-membership proof for quaternary tree
+/// Shows that the leaf slot assigned to `key_hash` holds `EMPTY_LEAF_HASH`
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct QuadTreeNonMembershipProof {
+    /// Hash of the excluded key; determines the leaf slot via `QuadTreeIndex::for_key`
+    pub key_hash: [u8; 32],
+    pub leaf_index: QuadTreeIndex,
+    /// Same layout as `QuadTreeMembershipProof::sibling_hashes` (LEAF to ROOT)
+    pub sibling_hashes: Vec<[[u8; 32]; 3]>,
+    pub root_hash: [u8; 32],
+}
 
-            // Reconstruct the 4 children
-            let mut children = [[0u8; 32]; 4];
-            let mut sibling_idx = 0;
-
-            for i in 0..4 {
-                if i == branch_index {
-                    children[i] = current_hash;
-                } else {
-                    if sibling_idx >= 3 {
-                        return false;
-                    }
-                    children[i] = siblings[sibling_idx];
-                    sibling_idx += 1;
-                }
-            }
+impl QuadTreeNonMembershipProof {
+    /// Verify the proof by reconstructing the root from an empty leaf
+    /// The leaf index must be the slot derived from `key_hash`
+    pub fn verify(&self) -> bool {
+        self.verify_with(Sha3Hasher::default())
+    }
 
-            current_hash = hash_node(&children[0], &children[1], &children[2], &children[3]);
+    /// Verify against a tree built with the given hasher
+    pub fn verify_with<H: QuadHasher>(&self, hasher: H) -> bool {
+        if self.leaf_index.depth > 128 {
+            return false;
+        }
+        if self.leaf_index != QuadTreeIndex::for_key(&self.key_hash, self.leaf_index.depth) {
+            return false;
         }
 
-        current_hash == self.root_hash
+        match reconstruct_root(
+            hasher,
+            &self.leaf_index,
+            &EMPTY_LEAF_HASH,
+            &self.sibling_hashes,
+        ) {
+            Ok(root) => root == self.root_hash,
+            Err(_) => false,
+        }
     }
 
     pub fn size_bytes(&self) -> usize {
@@ -157,6 +535,141 @@ mod tests {
         assert_eq!(child.path, vec![2]);
     }
 
+    #[test]
+    fn test_quad_index_fallible_constructors() {
+        assert_eq!(
+            QuadTreeIndex::try_new(2, vec![1]),
+            Err(IndexError::DepthMismatch {
+                depth: 2,
+                path_len: 1
+            })
+        );
+        assert_eq!(
+            QuadTreeIndex::try_new(2, vec![1, 4]),
+            Err(IndexError::InvalidBranch {
+                level: 1,
+                branch: 4
+            })
+        );
+        assert_eq!(
+            QuadTreeIndex::root().try_child(2),
+            Ok(QuadTreeIndex::new(1, vec![2]))
+        );
+        assert_eq!(
+            QuadTreeIndex::root().try_child(9),
+            Err(IndexError::InvalidBranch {
+                level: 0,
+                branch: 9
+            })
+        );
+        assert_eq!(
+            QuadTreeIndex::new(255, vec![0; 255]).try_child(0),
+            Err(IndexError::TooDeep)
+        );
+    }
+
+    #[test]
+    fn test_deserialization_rejects_inconsistent_index() {
+        let index: Result<QuadTreeIndex, _> =
+            serde_json::from_str(r#"{"depth": 3, "path": [0, 1]}"#);
+        assert!(index.is_err());
+
+        // Bincode proof whose index claims depth 2 but carries 1 path digit
+        let proof = QuadTreeMembershipProof {
+            leaf_index: QuadTreeIndex::new(1, vec![1]),
+            leaf_hash: [1u8; 32],
+            sibling_hashes: vec![[[2u8; 32]; 3]],
+            root_hash: [3u8; 32],
+        };
+        let mut bytes = bincode::serialize(&proof).unwrap();
+        bytes[0] = 2;
+        assert!(bincode::deserialize::<QuadTreeMembershipProof>(&bytes).is_err());
+
+        let mut bad_digit = bincode::serialize(&proof).unwrap();
+        bad_digit[9] = 4;
+        assert!(bincode::deserialize::<QuadTreeMembershipProof>(&bad_digit).is_err());
+    }
+
+    #[test]
+    fn test_quad_index_leaf_number_round_trip() {
+        let index = QuadTreeIndex::from_leaf_number(6, 3);
+        assert_eq!(index.path, vec![0, 1, 2]);
+        assert_eq!(index.leaf_number(), 6);
+
+        let leaves: Vec<_> = QuadTreeIndex::leaves(2).collect();
+        assert_eq!(leaves.len(), 16);
+        assert!(leaves.windows(2).all(|pair| pair[0] < pair[1]));
+        assert!(leaves
+            .iter()
+            .enumerate()
+            .all(|(i, leaf)| leaf.leaf_number() == i as u64));
+
+        let deepest = QuadTreeIndex::from_leaf_number(u64::MAX, 32);
+        assert!(deepest.path.iter().all(|&branch| branch == 3));
+        assert_eq!(deepest.leaf_number(), u64::MAX);
+    }
+
+    #[test]
+    fn test_quad_index_navigation() {
+        let index = QuadTreeIndex::new(3, vec![1, 3, 3]);
+        assert_eq!(index.parent(), Some(QuadTreeIndex::new(2, vec![1, 3])));
+        assert_eq!(index.ancestor(1), Some(QuadTreeIndex::new(1, vec![1])));
+        assert_eq!(index.ancestor(0), Some(QuadTreeIndex::root()));
+        assert_eq!(index.ancestor(4), None);
+        assert_eq!(QuadTreeIndex::root().parent(), None);
+
+        assert_eq!(
+            index.right_neighbor(),
+            Some(QuadTreeIndex::new(3, vec![2, 0, 0]))
+        );
+        assert_eq!(
+            index.left_neighbor(),
+            Some(QuadTreeIndex::new(3, vec![1, 3, 2]))
+        );
+        assert_eq!(
+            QuadTreeIndex::new(2, vec![1, 0]).left_neighbor(),
+            Some(QuadTreeIndex::new(2, vec![0, 3]))
+        );
+        assert_eq!(QuadTreeIndex::new(2, vec![0, 0]).left_neighbor(), None);
+        assert_eq!(QuadTreeIndex::new(2, vec![3, 3]).right_neighbor(), None);
+    }
+
+    #[test]
+    fn test_quad_index_packing() {
+        let index = QuadTreeIndex::new(5, vec![3, 0, 1, 2, 3]);
+        let packed = index.pack().unwrap();
+        assert_eq!(packed, vec![0b11_00_01_10, 0b11_00_00_00]);
+        assert_eq!(QuadTreeIndex::unpack(5, &packed), Some(index));
+        assert_eq!(QuadTreeIndex::unpack(5, &packed[..1]), None);
+        assert_eq!(QuadTreeIndex::unpack(5, &[packed[0], packed[1], 0]), None);
+
+        // Every unused low bit of the last byte must be zero
+        for bit in 0..6 {
+            assert_eq!(
+                QuadTreeIndex::unpack(5, &[packed[0], packed[1] | 1 << bit]),
+                None
+            );
+        }
+        assert!(QuadTreeIndex::unpack(4, &[0xff]).is_some());
+
+        // Packing matches the slot layout used for key hashes
+        let key_hash = [0b01_10_11_00u8; 32];
+        let slot = QuadTreeIndex::for_key(&key_hash, 7);
+        assert_eq!(slot.path, vec![1, 2, 3, 0, 1, 2, 3]);
+        assert_eq!(slot.pack().unwrap(), vec![0b01_10_11_00, 0b01_10_11_00]);
+
+        // Digits above 3 are rejected rather than masked into another path
+        let mut bad = slot;
+        bad.path[1] = 4;
+        assert_eq!(
+            bad.pack(),
+            Err(IndexError::InvalidBranch {
+                level: 1,
+                branch: 4
+            })
+        );
+    }
+
     #[test]
     fn test_hash_deterministic() {
         let data1 = b"test_data";
@@ -177,6 +690,30 @@ mod tests {
         assert_eq!(parent1, parent2);
     }
 
+    #[test]
+    fn test_hash_scheme_domain_separation() {
+        let children = [[1u8; 32], [2u8; 32], [3u8; 32], [4u8; 32]];
+        let concatenated = children.concat();
+
+        // Legacy keeps the original functions
+        assert_eq!(
+            HashScheme::Legacy.hash_node(1, &children[0], &children[1], &children[2], &children[3]),
+            hash_node(&children[0], &children[1], &children[2], &children[3])
+        );
+        assert_eq!(HashScheme::Legacy.hash_leaf(b"data"), hash_leaf(b"data"));
+
+        // V1: a 128-byte leaf never collides with a node over the same bytes
+        let v1_node =
+            HashScheme::V1.hash_node(1, &children[0], &children[1], &children[2], &children[3]);
+        assert_ne!(HashScheme::V1.hash_leaf(&concatenated), v1_node);
+
+        // V1 binds the height
+        assert_ne!(
+            v1_node,
+            HashScheme::V1.hash_node(2, &children[0], &children[1], &children[2], &children[3])
+        );
+    }
+
     #[test]
     fn test_proof_verification_simple() {
         let leaf0 = hash_leaf(b"leaf0");
@@ -250,6 +787,94 @@ mod tests {
         assert!(!proof.verify());
     }
 
+    #[test]
+    fn test_verify_detailed_reports_failure() {
+        let leaves = [
+            hash_leaf(b"leaf0"),
+            hash_leaf(b"leaf1"),
+            hash_leaf(b"leaf2"),
+            hash_leaf(b"leaf3"),
+        ];
+        let root = hash_node(&leaves[0], &leaves[1], &leaves[2], &leaves[3]);
+        let proof = QuadTreeMembershipProof {
+            leaf_index: QuadTreeIndex::new(1, vec![1]),
+            leaf_hash: leaves[1],
+            sibling_hashes: vec![[leaves[0], leaves[2], leaves[3]]],
+            root_hash: root,
+        };
+        assert_eq!(proof.verify_detailed(), Ok(()));
+
+        let mut wrong_leaf = proof.clone();
+        wrong_leaf.leaf_hash = hash_leaf(b"wrong");
+        assert!(matches!(
+            wrong_leaf.verify_detailed(),
+            Err(VerifyError::RootMismatch { expected, .. }) if expected == root
+        ));
+
+        let mut bad_digit = proof.clone();
+        bad_digit.leaf_index.path[0] = 7;
+        assert_eq!(
+            bad_digit.verify_detailed(),
+            Err(VerifyError::InvalidBranch {
+                level: 0,
+                branch: 7
+            })
+        );
+
+        let mut deeper = proof.clone();
+        deeper.leaf_index.depth = 2;
+        assert_eq!(
+            deeper.verify_detailed(),
+            Err(VerifyError::PathLengthMismatch {
+                depth: 2,
+                path_len: 1
+            })
+        );
+
+        let mut extra_level = proof;
+        extra_level.sibling_hashes.push([root; 3]);
+        assert_eq!(
+            extra_level.verify_detailed(),
+            Err(VerifyError::SiblingCountMismatch {
+                depth: 1,
+                sibling_levels: 2
+            })
+        );
+    }
+
+    #[test]
+    fn test_verify_against_trusted_root() {
+        let leaves = [
+            hash_leaf(b"leaf0"),
+            hash_leaf(b"leaf1"),
+            hash_leaf(b"leaf2"),
+            hash_leaf(b"leaf3"),
+        ];
+        let trusted_root = hash_node(&leaves[0], &leaves[1], &leaves[2], &leaves[3]);
+
+        // A self-consistent proof for a different tree passes verify() but not verify_against
+        let forged_leaf = hash_leaf(b"forged");
+        let forged = QuadTreeMembershipProof {
+            leaf_index: QuadTreeIndex::new(1, vec![1]),
+            leaf_hash: forged_leaf,
+            sibling_hashes: vec![[leaves[0], leaves[2], leaves[3]]],
+            root_hash: hash_node(&leaves[0], &forged_leaf, &leaves[2], &leaves[3]),
+        };
+        assert!(forged.verify());
+        assert!(!forged.verify_against(&trusted_root));
+        assert!(matches!(
+            forged.verify_detailed_against(&trusted_root, HashScheme::Legacy),
+            Err(VerifyError::RootMismatch { expected, .. }) if expected == trusted_root
+        ));
+
+        let genuine = QuadTreeMembershipProof {
+            leaf_hash: leaves[1],
+            root_hash: trusted_root,
+            ..forged
+        };
+        assert!(genuine.verify_against(&trusted_root));
+    }
+
     #[test]
     fn test_proof_verification_depth_2_simple() {
         let mut leaves = Vec::new();
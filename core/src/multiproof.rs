@@ -0,0 +1,251 @@
+use alloc::collections::{BTreeMap, BTreeSet};
+use alloc::vec::Vec;
+use serde::{Deserialize, Serialize};
+
+use crate::{QuadHasher, QuadTreeIndex, QuadTreeMembershipProof, Sha3Hasher};
+
+/// Membership proof for several leaves of the same quaternary tree
+///
+/// Siblings shared between paths, or recomputable from other proven leaves,
+/// are stored once (or not at all), so the proof grows with the number of
+/// distinct subtrees touched rather than with `leaves.len() * depth`.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct QuadTreeMultiProof {
+    pub depth: u8,
+    /// Proven leaves and their hashes
+    pub leaves: Vec<(QuadTreeIndex, [u8; 32])>,
+    /// Sibling hashes that cannot be recomputed from the proven leaves
+    /// Ordered LEAF to ROOT by level, then by parent path, then by child position
+    pub sibling_hashes: Vec<[u8; 32]>,
+    pub root_hash: [u8; 32],
+}
+
+impl QuadTreeMultiProof {
+    /// Combine individual membership proofs against the same root
+    /// Returns `None` if the proofs disagree on depth or root, or do not verify
+    /// under `hasher`
+    pub fn from_proofs<H: QuadHasher>(
+        proofs: &[QuadTreeMembershipProof],
+        hasher: H,
+    ) -> Option<Self> {
+        let first = proofs.first()?;
+        let depth = first.leaf_index.depth;
+        let root_hash = first.root_hash;
+
+        let mut leaves = Vec::with_capacity(proofs.len());
+        let mut known_siblings: BTreeMap<Vec<u8>, [u8; 32]> = BTreeMap::new();
+
+        for proof in proofs {
+            if proof.leaf_index.depth != depth
+                || proof.root_hash != root_hash
+                || !proof.verify_with(hasher)
+            {
+                return None;
+            }
+
+            // Record every sibling by its position in the tree
+            for (level_from_leaf, siblings) in proof.sibling_hashes.iter().enumerate() {
+                let path_level = depth as usize - 1 - level_from_leaf;
+                let branch = proof.leaf_index.path[path_level];
+                let mut position = proof.leaf_index.path[..=path_level].to_vec();
+                let mut sibling_idx = 0;
+
+                for i in 0..4u8 {
+                    if i != branch {
+                        position[path_level] = i;
+                        known_siblings.insert(position.clone(), siblings[sibling_idx]);
+                        sibling_idx += 1;
+                    }
+                }
+            }
+
+            leaves.push((proof.leaf_index.clone(), proof.leaf_hash));
+        }
+
+        leaves.sort();
+        leaves.dedup();
+
+        // Keep only the siblings the verifier will actually ask for
+        let mut sibling_hashes = Vec::new();
+        fold_to_root(hasher, depth, &leaves, |position| {
+            let hash = *known_siblings.get(position)?;
+            sibling_hashes.push(hash);
+            Some(hash)
+        })?;
+
+        Some(Self {
+            depth,
+            leaves,
+            sibling_hashes,
+            root_hash,
+        })
+    }
+
+    /// Verify all leaves at once by reconstructing the shared root
+    pub fn verify(&self) -> bool {
+        self.verify_with(Sha3Hasher::default())
+    }
+
+    /// Verify against a tree built with the given hasher
+    pub fn verify_with<H: QuadHasher>(&self, hasher: H) -> bool {
+        let mut siblings = self.sibling_hashes.iter();
+        let root = fold_to_root(hasher, self.depth, &self.leaves, |_| {
+            siblings.next().copied()
+        });
+
+        match root {
+            // Every supplied sibling must have been consumed
+            Some(root) => siblings.next().is_none() && root == self.root_hash,
+            None => false,
+        }
+    }
+
+    pub fn size_bytes(&self) -> usize {
+        let leaf_size = self.leaves.len() * (32 + 1 + self.depth as usize);
+        let sibling_size = self.sibling_hashes.len() * 32;
+        32 + 1 + leaf_size + sibling_size
+    }
+}
+
+/// Hash the proven leaves up to the root, one level at a time
+/// `next_sibling` supplies the hash of every child position not covered
+/// by the proven leaves, in the order documented on `sibling_hashes`
+fn fold_to_root<H: QuadHasher>(
+    hasher: H,
+    depth: u8,
+    leaves: &[(QuadTreeIndex, [u8; 32])],
+    mut next_sibling: impl FnMut(&[u8]) -> Option<[u8; 32]>,
+) -> Option<[u8; 32]> {
+    let mut level: BTreeMap<Vec<u8>, [u8; 32]> = BTreeMap::new();
+
+    for (index, hash) in leaves {
+        if index.depth != depth
+            || index.path.len() != depth as usize
+            || index.path.iter().any(|&branch| branch >= 4)
+        {
+            return None;
+        }
+        // Each leaf may be proven only once
+        if level.insert(index.path.clone(), *hash).is_some() {
+            return None;
+        }
+    }
+
+    if level.is_empty() {
+        return None;
+    }
+
+    for path_level in (0..depth as usize).rev() {
+        let parent_paths: BTreeSet<Vec<u8>> = level
+            .keys()
+            .map(|path| path[..path_level].to_vec())
+            .collect();
+        let mut parents = BTreeMap::new();
+
+        for parent in parent_paths {
+            let mut children = [[0u8; 32]; 4];
+            let mut position = parent.clone();
+            position.push(0);
+
+            for (i, child) in children.iter_mut().enumerate() {
+                position[path_level] = i as u8;
+                *child = match level.get(&position) {
+                    Some(hash) => *hash,
+                    None => next_sibling(&position)?,
+                };
+            }
+
+            let height = (depth as usize - path_level) as u8;
+            parents.insert(
+                parent,
+                hasher.hash_node(
+                    height,
+                    &children[0],
+                    &children[1],
+                    &children[2],
+                    &children[3],
+                ),
+            );
+        }
+
+        level = parents;
+    }
+
+    level.get(&Vec::new()).copied()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{hash_leaf, HashScheme, SparseQuadTree};
+
+    fn full_tree(depth: u8) -> SparseQuadTree {
+        let mut tree = SparseQuadTree::new(depth);
+        for i in 0..4u32.pow(depth as u32) {
+            let path: Vec<u8> = (0..depth)
+                .rev()
+                .map(|level| ((i >> (2 * level as u32)) & 0b11) as u8)
+                .collect();
+            tree.insert(&path, hash_leaf(format!("leaf{}", i).as_bytes()));
+        }
+        tree
+    }
+
+    #[test]
+    fn test_multi_proof_deduplicates_siblings() {
+        let tree = full_tree(3);
+        let paths = [[0u8, 1, 2], [0, 1, 3], [0, 2, 0], [3, 3, 3]];
+        let proofs: Vec<_> = paths
+            .iter()
+            .map(|path| tree.generate_membership_proof(path).unwrap())
+            .collect();
+
+        let multi = QuadTreeMultiProof::from_proofs(&proofs, HashScheme::Legacy).unwrap();
+        assert!(multi.verify());
+        assert_eq!(multi.root_hash, tree.root());
+
+        // [0,1,2] and [0,1,3] share a parent; [0,*] and [3,*] share the root level
+        let individual: usize = proofs.iter().map(|p| p.sibling_hashes.len() * 3).sum();
+        assert_eq!(individual, 36);
+        assert_eq!(multi.sibling_hashes.len(), 2 + 3 + 3 + 3 + 2 + 2);
+    }
+
+    #[test]
+    fn test_multi_proof_all_leaves_needs_no_siblings() {
+        let tree = full_tree(2);
+        let proofs: Vec<_> = (0..4u8)
+            .flat_map(|i| (0..4u8).map(move |j| [i, j]))
+            .map(|path| tree.generate_membership_proof(&path).unwrap())
+            .collect();
+
+        let multi = QuadTreeMultiProof::from_proofs(&proofs, HashScheme::Legacy).unwrap();
+        assert!(multi.sibling_hashes.is_empty());
+        assert!(multi.verify());
+    }
+
+    #[test]
+    fn test_multi_proof_tamper_detection() {
+        let tree = full_tree(2);
+        let proofs: Vec<_> = [[1u8, 0], [2, 3]]
+            .iter()
+            .map(|path| tree.generate_membership_proof(path).unwrap())
+            .collect();
+        let multi = QuadTreeMultiProof::from_proofs(&proofs, HashScheme::Legacy).unwrap();
+
+        let mut wrong_leaf = multi.clone();
+        wrong_leaf.leaves[0].1[0] ^= 0xFF;
+        assert!(!wrong_leaf.verify());
+
+        let mut extra_sibling = multi.clone();
+        extra_sibling.sibling_hashes.push([0u8; 32]);
+        assert!(!extra_sibling.verify());
+
+        let mut missing_sibling = multi.clone();
+        missing_sibling.sibling_hashes.pop();
+        assert!(!missing_sibling.verify());
+
+        let mut duplicate_leaf = multi;
+        duplicate_leaf.leaves.push(duplicate_leaf.leaves[0].clone());
+        assert!(!duplicate_leaf.verify());
+    }
+}
@@ -0,0 +1,145 @@
+use serde::{Deserialize, Serialize};
+use sha3::{Digest, Sha3_256};
+
+use crate::{QuadHasher, QuadTreeMembershipProof, Sha3Hasher};
+
+const IDENTITY_PREFIX: &[u8; 14] = b"QUAD_IDENTITY:";
+
+/// Leaf data committing to a member's nullifier secret
+///
+/// Trees used for nullifiers hold `hash_leaf(identity_leaf(secret))` for each
+/// member, so every leaf admits exactly one secret.
+pub fn identity_leaf(secret: &[u8; 32]) -> [u8; 46] {
+    let mut leaf = [0u8; 46];
+    leaf[..IDENTITY_PREFIX.len()].copy_from_slice(IDENTITY_PREFIX);
+    leaf[IDENTITY_PREFIX.len()..].copy_from_slice(secret);
+    leaf
+}
+
+/// Leaf hash of a member's identity commitment (legacy SHA3-256)
+pub fn identity_commitment(secret: &[u8; 32]) -> [u8; 32] {
+    identity_commitment_with(secret, Sha3Hasher::default())
+}
+
+/// `identity_commitment` for a tree built with the given hasher
+pub fn identity_commitment_with<H: QuadHasher>(secret: &[u8; 32], hasher: H) -> [u8; 32] {
+    hasher.hash_leaf(&identity_leaf(secret))
+}
+
+/// Derive the nullifier for one use of a leaf under an external nullifier
+/// (e.g. a poll or epoch id)
+///
+/// The same secret and leaf always yield the same nullifier for a given
+/// external nullifier, so a verifier can reject repeats without learning the
+/// leaf. The secret keeps nullifiers unlinkable to leaf hashes; the leaf must
+/// be the secret's identity commitment, which `QuadTreeNullifierProof::verify`
+/// checks, so a member cannot produce fresh nullifiers by changing the secret.
+pub fn hash_nullifier(
+    secret: &[u8; 32],
+    leaf_hash: &[u8; 32],
+    external_nullifier: &[u8; 32],
+) -> [u8; 32] {
+    let mut hasher = Sha3_256::new();
+    hasher.update(b"QUAD_NULLIFIER:");
+    hasher.update(secret);
+    hasher.update(leaf_hash);
+    hasher.update(external_nullifier);
+    hasher.finalize().into()
+}
+
+/// Membership proof plus the private inputs needed to derive a nullifier
+/// Only the root, nullifier and external nullifier are meant to be public
+///
+/// The proven leaf must be `identity_commitment(secret)`; verification
+/// recomputes it from the secret before walking the path.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct QuadTreeNullifierProof {
+    pub membership: QuadTreeMembershipProof,
+    pub secret: [u8; 32],
+    pub external_nullifier: [u8; 32],
+}
+
+impl QuadTreeNullifierProof {
+    /// Recompute the identity commitment from the secret, then walk the path to the root
+    pub fn verify(&self) -> bool {
+        self.verify_with(Sha3Hasher::default())
+    }
+
+    /// Verify against a tree built with the given hasher
+    pub fn verify_with<H: QuadHasher>(&self, hasher: H) -> bool {
+        identity_commitment_with(&self.secret, hasher) == self.membership.leaf_hash
+            && self.membership.verify_with(hasher)
+    }
+
+    pub fn nullifier(&self) -> [u8; 32] {
+        hash_nullifier(
+            &self.secret,
+            &self.membership.leaf_hash,
+            &self.external_nullifier,
+        )
+    }
+
+    pub fn root_hash(&self) -> [u8; 32] {
+        self.membership.root_hash
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{hash_leaf, hash_node, QuadTreeIndex};
+
+    fn nullifier_proof(secret: [u8; 32], external_nullifier: [u8; 32]) -> QuadTreeNullifierProof {
+        let leaves = [
+            hash_leaf(b"leaf0"),
+            hash_leaf(b"leaf1"),
+            identity_commitment(&secret),
+            hash_leaf(b"leaf3"),
+        ];
+        QuadTreeNullifierProof {
+            membership: QuadTreeMembershipProof {
+                leaf_index: QuadTreeIndex::new(1, vec![2]),
+                leaf_hash: leaves[2],
+                sibling_hashes: vec![[leaves[0], leaves[1], leaves[3]]],
+                root_hash: hash_node(&leaves[0], &leaves[1], &leaves[2], &leaves[3]),
+            },
+            secret,
+            external_nullifier,
+        }
+    }
+
+    #[test]
+    fn test_nullifier_deterministic_per_external_nullifier() {
+        let first = nullifier_proof([1u8; 32], [9u8; 32]);
+        let repeat = nullifier_proof([1u8; 32], [9u8; 32]);
+        let next_epoch = nullifier_proof([1u8; 32], [10u8; 32]);
+
+        assert!(first.verify());
+        assert_eq!(first.nullifier(), repeat.nullifier());
+        assert_ne!(first.nullifier(), next_epoch.nullifier());
+    }
+
+    #[test]
+    fn test_nullifier_hides_leaf_hash() {
+        let proof = nullifier_proof([1u8; 32], [9u8; 32]);
+        let other_secret = nullifier_proof([2u8; 32], [9u8; 32]);
+
+        // Without the secret, the leaf hash alone does not determine the nullifier
+        assert_ne!(proof.nullifier(), other_secret.nullifier());
+        assert_ne!(proof.nullifier(), proof.membership.leaf_hash);
+    }
+
+    #[test]
+    fn test_nullifier_requires_leaf_secret() {
+        let proof = nullifier_proof([1u8; 32], [9u8; 32]);
+        assert!(proof.verify());
+
+        // Same leaf and path, different secret: the commitment no longer matches
+        let forged = QuadTreeNullifierProof {
+            secret: [2u8; 32],
+            ..proof.clone()
+        };
+        assert!(forged.membership.verify());
+        assert!(!forged.verify());
+    }
+}
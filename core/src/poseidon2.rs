@@ -0,0 +1,128 @@
+use p3_field::PrimeField32;
+use p3_koala_bear::{default_koalabear_poseidon2_16, KoalaBear, Poseidon2KoalaBear};
+use p3_symmetric::Permutation;
+
+use crate::{HashScheme, QuadHasher};
+
+/// Poseidon2 state width and sponge rate, in KoalaBear elements
+const WIDTH: usize = 16;
+const RATE: usize = 8;
+
+/// Bytes packed into each absorbed element (24 bits, always below the modulus)
+const BYTES_PER_ELEMENT: usize = 3;
+
+/// Width-16 Poseidon2 permutation over KoalaBear used by `Poseidon2Hasher`
+pub type Poseidon2Permutation = Poseidon2KoalaBear<WIDTH>;
+
+/// Poseidon2 over KoalaBear, the field of Pico's STARK prover
+///
+/// Width-16 permutation in a rate-8 overwrite sponge. Input bytes are packed
+/// three per element with `10*` byte padding, so the encoding is injective
+/// for any input length. The digest is the first 8 state elements, each
+/// written as its canonical value in 4 little-endian bytes.
+///
+/// An algebraic hash needs far fewer field operations than SHA3 per node,
+/// which is where guest cycles go for deep trees.
+///
+/// Building the permutation copies all its round constants, so the hasher
+/// borrows one built up front with `Poseidon2Hasher::permutation` and every
+/// copy of the hasher shares it.
+#[derive(Debug, Clone, Copy)]
+pub struct Poseidon2Hasher<'a> {
+    permutation: &'a Poseidon2Permutation,
+    scheme: HashScheme,
+}
+
+impl<'a> Poseidon2Hasher<'a> {
+    pub fn new(permutation: &'a Poseidon2Permutation, scheme: HashScheme) -> Self {
+        Self {
+            permutation,
+            scheme,
+        }
+    }
+
+    /// Build the permutation once and share it between hashers
+    pub fn permutation() -> Poseidon2Permutation {
+        default_koalabear_poseidon2_16()
+    }
+}
+
+impl QuadHasher for Poseidon2Hasher<'_> {
+    fn digest(&self, parts: &[&[u8]]) -> [u8; 32] {
+        let permutation = self.permutation;
+        let mut state = [KoalaBear::new(0); WIDTH];
+        let mut absorbed = 0;
+        let mut chunk = [0u8; BYTES_PER_ELEMENT];
+        let mut chunk_len = 0;
+
+        let mut absorb = |element: KoalaBear, state: &mut [KoalaBear; WIDTH]| {
+            state[absorbed] = element;
+            absorbed += 1;
+            if absorbed == RATE {
+                permutation.permute_mut(state);
+                absorbed = 0;
+            }
+        };
+
+        let bytes = parts.iter().flat_map(|part| part.iter().copied());
+        for byte in bytes.chain(core::iter::once(0x01)) {
+            chunk[chunk_len] = byte;
+            chunk_len += 1;
+            if chunk_len == BYTES_PER_ELEMENT {
+                absorb(pack(&chunk), &mut state);
+                chunk = [0u8; BYTES_PER_ELEMENT];
+                chunk_len = 0;
+            }
+        }
+        if chunk_len > 0 {
+            absorb(pack(&chunk), &mut state);
+        }
+
+        // Zero-fill the last block; the padding byte keeps lengths apart
+        if absorbed > 0 {
+            state[absorbed..RATE].fill(KoalaBear::new(0));
+            permutation.permute_mut(&mut state);
+        }
+
+        let mut output = [0u8; 32];
+        for (bytes, element) in output.chunks_exact_mut(4).zip(&state[..RATE]) {
+            bytes.copy_from_slice(&element.as_canonical_u32().to_le_bytes());
+        }
+        output
+    }
+
+    fn scheme(&self) -> HashScheme {
+        self.scheme
+    }
+}
+
+fn pack(chunk: &[u8; BYTES_PER_ELEMENT]) -> KoalaBear {
+    KoalaBear::new(u32::from_le_bytes([chunk[0], chunk[1], chunk[2], 0]))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::SparseQuadTree;
+
+    #[test]
+    fn test_poseidon2_padding_separates_lengths() {
+        let permutation = Poseidon2Hasher::permutation();
+        let hasher = Poseidon2Hasher::new(&permutation, HashScheme::Legacy);
+        assert_ne!(hasher.digest(&[]), hasher.digest(&[&[0]]));
+        assert_ne!(hasher.digest(&[&[0; 23]]), hasher.digest(&[&[0; 24]]));
+        assert_eq!(hasher.digest(&[b"ab", b"c"]), hasher.digest(&[b"abc"]));
+    }
+
+    #[test]
+    fn test_poseidon2_tree() {
+        let permutation = Poseidon2Hasher::permutation();
+        let hasher = Poseidon2Hasher::new(&permutation, HashScheme::V1);
+        let mut tree = SparseQuadTree::with_hasher(4, hasher);
+        tree.insert(&[3, 1, 0, 2], hasher.hash_leaf(b"member"));
+
+        let proof = tree.generate_membership_proof(&[3, 1, 0, 2]).unwrap();
+        assert!(proof.verify_with(hasher));
+        assert!(!proof.verify_with(HashScheme::V1));
+    }
+}
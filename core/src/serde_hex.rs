@@ -0,0 +1,210 @@
+//! Human-readable serde representation for proof fields
+//!
+//! Formats that report `is_human_readable()` (JSON) get hashes as lowercase
+//! hex strings and paths as digit strings such as `"012"`. Binary formats
+//! (bincode, which the guest reads) keep the plain derived encoding.
+
+use alloc::string::String;
+use alloc::vec::Vec;
+use serde::de::Error;
+use serde::ser::SerializeSeq;
+use serde::{Deserialize, Deserializer, Serialize, Serializer};
+
+use crate::QuadTreeIndex;
+
+const HEX_DIGITS: &[u8; 16] = b"0123456789abcdef";
+
+fn encode(hash: &[u8; 32]) -> String {
+    let mut hex = String::with_capacity(64);
+    for byte in hash {
+        hex.push(HEX_DIGITS[(byte >> 4) as usize] as char);
+        hex.push(HEX_DIGITS[(byte & 0x0F) as usize] as char);
+    }
+    hex
+}
+
+fn decode<E: Error>(hex: &str) -> Result<[u8; 32], E> {
+    fn nibble<E: Error>(digit: u8) -> Result<u8, E> {
+        match digit {
+            b'0'..=b'9' => Ok(digit - b'0'),
+            b'a'..=b'f' => Ok(digit - b'a' + 10),
+            b'A'..=b'F' => Ok(digit - b'A' + 10),
+            _ => Err(E::custom("hash must be hex")),
+        }
+    }
+
+    let digits = hex.as_bytes();
+    if digits.len() != 64 {
+        return Err(E::invalid_length(digits.len(), &"64 hex digits"));
+    }
+    let mut hash = [0u8; 32];
+    for (byte, pair) in hash.iter_mut().zip(digits.chunks_exact(2)) {
+        *byte = (nibble::<E>(pair[0])? << 4) | nibble::<E>(pair[1])?;
+    }
+    Ok(hash)
+}
+
+struct Hex<'a>(&'a [u8; 32]);
+
+impl Serialize for Hex<'_> {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        serializer.serialize_str(&encode(self.0))
+    }
+}
+
+/// `[u8; 32]` as a hex string
+pub(crate) mod hash {
+    use super::*;
+
+    pub(crate) fn serialize<S: Serializer>(
+        hash: &[u8; 32],
+        serializer: S,
+    ) -> Result<S::Ok, S::Error> {
+        if serializer.is_human_readable() {
+            Hex(hash).serialize(serializer)
+        } else {
+            hash.serialize(serializer)
+        }
+    }
+
+    pub(crate) fn deserialize<'de, D: Deserializer<'de>>(
+        deserializer: D,
+    ) -> Result<[u8; 32], D::Error> {
+        if deserializer.is_human_readable() {
+            decode(&String::deserialize(deserializer)?)
+        } else {
+            <[u8; 32]>::deserialize(deserializer)
+        }
+    }
+}
+
+/// Sibling triples as arrays of three hex strings
+pub(crate) mod sibling_triples {
+    use super::*;
+
+    pub(crate) fn serialize<S: Serializer>(
+        triples: &[[[u8; 32]; 3]],
+        serializer: S,
+    ) -> Result<S::Ok, S::Error> {
+        if !serializer.is_human_readable() {
+            return triples.serialize(serializer);
+        }
+        let mut seq = serializer.serialize_seq(Some(triples.len()))?;
+        for [a, b, c] in triples {
+            seq.serialize_element(&[Hex(a), Hex(b), Hex(c)])?;
+        }
+        seq.end()
+    }
+
+    pub(crate) fn deserialize<'de, D: Deserializer<'de>>(
+        deserializer: D,
+    ) -> Result<Vec<[[u8; 32]; 3]>, D::Error> {
+        if !deserializer.is_human_readable() {
+            return Vec::deserialize(deserializer);
+        }
+        Vec::<[String; 3]>::deserialize(deserializer)?
+            .iter()
+            .map(|[a, b, c]| Ok([decode(a)?, decode(b)?, decode(c)?]))
+            .collect()
+    }
+}
+
+/// `QuadTreeIndex` as its path digits, root first (`"012"`; depth is the length)
+pub(crate) mod path {
+    use super::*;
+
+    pub(crate) fn serialize<S: Serializer>(
+        index: &QuadTreeIndex,
+        serializer: S,
+    ) -> Result<S::Ok, S::Error> {
+        if !serializer.is_human_readable() {
+            return index.serialize(serializer);
+        }
+        let digits: String = index
+            .path
+            .iter()
+            .map(|&branch| (b'0' + branch) as char)
+            .collect();
+        serializer.serialize_str(&digits)
+    }
+
+    pub(crate) fn deserialize<'de, D: Deserializer<'de>>(
+        deserializer: D,
+    ) -> Result<QuadTreeIndex, D::Error> {
+        if !deserializer.is_human_readable() {
+            return QuadTreeIndex::deserialize(deserializer);
+        }
+        let digits = String::deserialize(deserializer)?;
+        let depth = u8::try_from(digits.len())
+            .map_err(|_| D::Error::custom("path longer than 255 levels"))?;
+        let path = digits
+            .bytes()
+            .map(|digit| match digit {
+                b'0'..=b'3' => Ok(digit - b'0'),
+                _ => Err(D::Error::custom("path digits must be 0-3")),
+            })
+            .collect::<Result<Vec<u8>, _>>()?;
+        QuadTreeIndex::try_new(depth, path).map_err(D::Error::custom)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::{hash_leaf, QuadTreeMembershipProof, SparseQuadTree};
+
+    fn proof() -> QuadTreeMembershipProof {
+        let mut tree = SparseQuadTree::new(3);
+        tree.insert(&[0, 1, 2], hash_leaf(b"member"));
+        tree.generate_membership_proof(&[0, 1, 2]).unwrap()
+    }
+
+    #[test]
+    fn test_json_uses_hex_and_digit_path() {
+        let proof = proof();
+        let json = serde_json::to_value(&proof).unwrap();
+
+        assert_eq!(json["leaf_index"], "012");
+        assert_eq!(json["leaf_hash"].as_str().unwrap().len(), 64);
+        assert_eq!(json["sibling_hashes"].as_array().unwrap().len(), 3);
+        assert!(json["sibling_hashes"][0][0].is_string());
+
+        let decoded: QuadTreeMembershipProof = serde_json::from_value(json).unwrap();
+        assert_eq!(decoded.leaf_index, proof.leaf_index);
+        assert_eq!(decoded.sibling_hashes, proof.sibling_hashes);
+        assert!(decoded.verify());
+    }
+
+    #[test]
+    fn test_bincode_layout_unchanged() {
+        let proof = proof();
+        let bytes = bincode::serialize(&proof).unwrap();
+
+        // Plain derive layout: depth, length-prefixed path, then raw hashes
+        let mut expected = vec![3u8];
+        expected.extend_from_slice(&3u64.to_le_bytes());
+        expected.extend_from_slice(&[0, 1, 2]);
+        expected.extend_from_slice(&proof.leaf_hash);
+        expected.extend_from_slice(&3u64.to_le_bytes());
+        for siblings in &proof.sibling_hashes {
+            for sibling in siblings {
+                expected.extend_from_slice(sibling);
+            }
+        }
+        expected.extend_from_slice(&proof.root_hash);
+        assert_eq!(bytes, expected);
+
+        let decoded: QuadTreeMembershipProof = bincode::deserialize(&bytes).unwrap();
+        assert_eq!(decoded.root_hash, proof.root_hash);
+    }
+
+    #[test]
+    fn test_json_rejects_bad_digits() {
+        let mut json = serde_json::to_value(proof()).unwrap();
+        json["leaf_index"] = "014".into();
+        assert!(serde_json::from_value::<QuadTreeMembershipProof>(json.clone()).is_err());
+
+        json["leaf_index"] = "012".into();
+        json["root_hash"] = "zz".into();
+        assert!(serde_json::from_value::<QuadTreeMembershipProof>(json).is_err());
+    }
+}
@@ -0,0 +1,419 @@
+use alloc::collections::BTreeMap;
+use alloc::vec::Vec;
+
+use crate::{
+    HashScheme, QuadHasher, QuadTreeIndex, QuadTreeMembershipProof, QuadTreeNonMembershipProof,
+    EMPTY_LEAF_HASH,
+};
+
+/// Hashes of empty subtrees, indexed by height above the leaves
+/// (0 = empty leaf, `depth` = root of an entirely empty tree)
+pub fn default_hashes(depth: u8) -> Vec<[u8; 32]> {
+    default_hashes_with(depth, HashScheme::Legacy)
+}
+
+/// `default_hashes` for a tree built with the given hasher
+pub fn default_hashes_with<H: QuadHasher>(depth: u8, hasher: H) -> Vec<[u8; 32]> {
+    let mut hashes = Vec::with_capacity(depth as usize + 1);
+    hashes.push(EMPTY_LEAF_HASH);
+
+    for height in 1..=depth as usize {
+        let below = hashes[height - 1];
+        hashes.push(hasher.hash_node(height as u8, &below, &below, &below, &below));
+    }
+
+    hashes
+}
+
+/// Smallest depth whose 4^depth leaf slots hold `count` leaves (at least 1)
+pub fn depth_for_leaf_count(count: u64) -> u8 {
+    let mut depth = 1;
+    while 4u128.pow(depth as u32) < count as u128 {
+        depth += 1;
+    }
+    depth
+}
+
+/// Sparse quaternary Merkle tree
+///
+/// Only nodes whose hash differs from the empty-subtree default are stored,
+/// so memory grows with the number of occupied leaves times the depth
+/// instead of with 4^depth.
+#[derive(Debug, Clone)]
+pub struct SparseQuadTree<H: QuadHasher = HashScheme> {
+    depth: u8,
+    hasher: H,
+    /// Non-default node hashes keyed by their position (root included)
+    nodes: BTreeMap<QuadTreeIndex, [u8; 32]>,
+    /// Empty-subtree hashes indexed by height above the leaves
+    defaults: Vec<[u8; 32]>,
+}
+
+impl SparseQuadTree {
+    /// Create an empty tree with 4^depth leaf slots (legacy SHA3-256 hashing)
+    pub fn new(depth: u8) -> Self {
+        Self::with_hasher(depth, HashScheme::Legacy)
+    }
+
+    /// Tree over caller-supplied leaf values, hashed with `hash_leaf`
+    /// See `from_leaf_hashes_with` for placement and padding
+    pub fn from_leaves<I>(leaves: I) -> Self
+    where
+        I: IntoIterator,
+        I::Item: AsRef<[u8]>,
+    {
+        Self::from_leaves_with(leaves, HashScheme::Legacy)
+    }
+
+    /// Tree over pre-hashed leaves (legacy SHA3-256 hashing)
+    pub fn from_leaf_hashes(leaf_hashes: impl IntoIterator<Item = [u8; 32]>) -> Self {
+        Self::from_leaf_hashes_with(leaf_hashes, HashScheme::Legacy)
+    }
+}
+
+impl<H: QuadHasher> SparseQuadTree<H> {
+    /// Create an empty tree that hashes nodes with the given hasher
+    pub fn with_hasher(depth: u8, hasher: H) -> Self {
+        Self {
+            depth,
+            hasher,
+            nodes: BTreeMap::new(),
+            defaults: default_hashes_with(depth, hasher),
+        }
+    }
+
+    /// Tree over caller-supplied leaf values, each hashed with `hasher.hash_leaf`
+    pub fn from_leaves_with<I>(leaves: I, hasher: H) -> Self
+    where
+        I: IntoIterator,
+        I::Item: AsRef<[u8]>,
+    {
+        Self::from_leaf_hashes_with(
+            leaves
+                .into_iter()
+                .map(|leaf| hasher.hash_leaf(leaf.as_ref())),
+            hasher,
+        )
+    }
+
+    /// Tree over pre-hashed leaves
+    ///
+    /// Leaves fill slots left to right in iteration order. The depth is the
+    /// smallest that fits them all (`depth_for_leaf_count`), and the slots past
+    /// the last leaf are padded with `EMPTY_LEAF_HASH`.
+    pub fn from_leaf_hashes_with(
+        leaf_hashes: impl IntoIterator<Item = [u8; 32]>,
+        hasher: H,
+    ) -> Self {
+        let leaf_hashes: Vec<[u8; 32]> = leaf_hashes.into_iter().collect();
+        let depth = depth_for_leaf_count(leaf_hashes.len() as u64);
+        let mut tree = Self::with_hasher(depth, hasher);
+
+        for (position, leaf_hash) in leaf_hashes.into_iter().enumerate() {
+            let index = QuadTreeIndex::from_leaf_number(position as u64, depth);
+            tree.insert(&index.path, leaf_hash);
+        }
+
+        tree
+    }
+
+    pub fn depth(&self) -> u8 {
+        self.depth
+    }
+
+    pub fn hasher(&self) -> H {
+        self.hasher
+    }
+
+    pub fn root(&self) -> [u8; 32] {
+        self.node_hash(&QuadTreeIndex::root())
+    }
+
+    /// Number of occupied leaf slots
+    pub fn len(&self) -> usize {
+        self.nodes
+            .keys()
+            .filter(|index| index.depth == self.depth)
+            .count()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.nodes.is_empty()
+    }
+
+    /// Hash of the node at `index`, falling back to the empty-subtree default
+    pub fn node_hash(&self, index: &QuadTreeIndex) -> [u8; 32] {
+        match self.nodes.get(index) {
+            Some(hash) => *hash,
+            None => self.defaults[(self.depth - index.depth) as usize],
+        }
+    }
+
+    /// Hash stored in the leaf slot at `path` (`EMPTY_LEAF_HASH` if unoccupied)
+    pub fn leaf_hash(&self, path: &[u8]) -> [u8; 32] {
+        self.node_hash(&QuadTreeIndex::new(self.depth, path.to_vec()))
+    }
+
+    /// Store `leaf_hash` at `path` and return the new root
+    /// Only the `depth` ancestors of the leaf are rehashed
+    pub fn insert(&mut self, path: &[u8], leaf_hash: [u8; 32]) -> [u8; 32] {
+        let leaf_index = QuadTreeIndex::new(self.depth, path.to_vec());
+        self.set_node(leaf_index, leaf_hash);
+
+        // Walk from the leaf's parent up to the root
+        for level in (0..self.depth as usize).rev() {
+            let parent = QuadTreeIndex::new(level as u8, path[..level].to_vec());
+            let children = [
+                self.node_hash(&parent.child(0)),
+                self.node_hash(&parent.child(1)),
+                self.node_hash(&parent.child(2)),
+                self.node_hash(&parent.child(3)),
+            ];
+            let hash = self.hasher.hash_node(
+                self.depth - level as u8,
+                &children[0],
+                &children[1],
+                &children[2],
+                &children[3],
+            );
+            self.set_node(parent, hash);
+        }
+
+        self.root()
+    }
+
+    /// Generate a membership proof for the leaf at `path`
+    /// Returns `None` if the slot is unoccupied
+    pub fn generate_membership_proof(&self, path: &[u8]) -> Option<QuadTreeMembershipProof> {
+        let leaf_hash = self.leaf_hash(path);
+        if leaf_hash == EMPTY_LEAF_HASH {
+            return None;
+        }
+
+        Some(QuadTreeMembershipProof {
+            leaf_index: QuadTreeIndex::new(self.depth, path.to_vec()),
+            leaf_hash,
+            sibling_hashes: self.sibling_hashes(path),
+            root_hash: self.root(),
+        })
+    }
+
+    /// Store `key_hash` in its key-derived slot (see `QuadTreeIndex::for_key`)
+    /// and return the new root
+    /// Returns `None`, leaving the tree unchanged, if the slot already holds a
+    /// different key (the two keys share their first `2 * depth` bits)
+    pub fn insert_key(&mut self, key_hash: [u8; 32]) -> Option<[u8; 32]> {
+        let slot = QuadTreeIndex::for_key(&key_hash, self.depth);
+        let current = self.node_hash(&slot);
+        if current != EMPTY_LEAF_HASH && current != key_hash {
+            return None;
+        }
+        Some(self.insert(&slot.path, key_hash))
+    }
+
+    /// Generate a proof that the slot for `key_hash` is empty
+    /// Returns `None` if the slot is occupied
+    ///
+    /// Non-membership is only defined for keys whose slot is empty: a key whose
+    /// slot holds a different (colliding) key is absent from the tree, yet has
+    /// no proof either way.
+    pub fn generate_non_membership_proof(
+        &self,
+        key_hash: &[u8; 32],
+    ) -> Option<QuadTreeNonMembershipProof> {
+        let leaf_index = QuadTreeIndex::for_key(key_hash, self.depth);
+        if self.node_hash(&leaf_index) != EMPTY_LEAF_HASH {
+            return None;
+        }
+
+        Some(QuadTreeNonMembershipProof {
+            key_hash: *key_hash,
+            sibling_hashes: self.sibling_hashes(&leaf_index.path),
+            leaf_index,
+            root_hash: self.root(),
+        })
+    }
+
+    /// Sibling hashes along `path`, stored from LEAF to ROOT
+    pub(crate) fn sibling_hashes(&self, path: &[u8]) -> Vec<[[u8; 32]; 3]> {
+        let mut sibling_hashes = Vec::with_capacity(self.depth as usize);
+
+        for level in (0..self.depth as usize).rev() {
+            let parent = QuadTreeIndex::new(level as u8, path[..level].to_vec());
+            let mut siblings = [[0u8; 32]; 3];
+            let mut sibling_idx = 0;
+
+            for i in 0..4u8 {
+                if i != path[level] {
+                    siblings[sibling_idx] = self.node_hash(&parent.child(i));
+                    sibling_idx += 1;
+                }
+            }
+
+            sibling_hashes.push(siblings);
+        }
+
+        sibling_hashes
+    }
+
+    /// Store a node hash, dropping it if it equals the empty-subtree default
+    fn set_node(&mut self, index: QuadTreeIndex, hash: [u8; 32]) {
+        if hash == self.defaults[(self.depth - index.depth) as usize] {
+            self.nodes.remove(&index);
+        } else {
+            self.nodes.insert(index, hash);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{hash_leaf, hash_node};
+
+    #[test]
+    fn test_empty_tree_root_is_default() {
+        let tree = SparseQuadTree::new(3);
+        let defaults = default_hashes(3);
+
+        assert_eq!(tree.root(), defaults[3]);
+        assert!(tree.is_empty());
+    }
+
+    #[test]
+    fn test_sparse_matches_dense_depth_2() {
+        let mut tree = SparseQuadTree::new(2);
+        let mut leaves = [EMPTY_LEAF_HASH; 16];
+
+        for (i, path) in [[0u8, 1], [2, 3], [3, 0]].iter().enumerate() {
+            let leaf = hash_leaf(format!("leaf{}", i).as_bytes());
+            leaves[path[0] as usize * 4 + path[1] as usize] = leaf;
+            tree.insert(path, leaf);
+        }
+
+        let mut level1 = Vec::new();
+        for i in 0..4 {
+            level1.push(hash_node(
+                &leaves[i * 4],
+                &leaves[i * 4 + 1],
+                &leaves[i * 4 + 2],
+                &leaves[i * 4 + 3],
+            ));
+        }
+        let root = hash_node(&level1[0], &level1[1], &level1[2], &level1[3]);
+
+        assert_eq!(tree.root(), root);
+        assert_eq!(tree.len(), 3);
+    }
+
+    #[test]
+    fn test_sparse_proof_verifies_depth_16() {
+        let mut tree = SparseQuadTree::new(16);
+        let path = [3u8, 1, 0, 2, 2, 1, 3, 0, 0, 1, 2, 3, 3, 2, 1, 0];
+        let other = [0u8; 16];
+
+        tree.insert(&path, hash_leaf(b"member"));
+        tree.insert(&other, hash_leaf(b"other"));
+
+        let proof = tree.generate_membership_proof(&path).unwrap();
+        assert_eq!(proof.sibling_hashes.len(), 16);
+        assert_eq!(proof.root_hash, tree.root());
+        assert!(proof.verify());
+
+        assert!(tree.generate_membership_proof(&[1u8; 16]).is_none());
+    }
+
+    #[test]
+    fn test_v1_scheme_tree() {
+        let mut legacy = SparseQuadTree::new(4);
+        let mut v1 = SparseQuadTree::with_hasher(4, HashScheme::V1);
+        let path = [1u8, 2, 3, 0];
+        legacy.insert(&path, hash_leaf(b"member"));
+        v1.insert(&path, HashScheme::V1.hash_leaf(b"member"));
+
+        assert_ne!(legacy.root(), v1.root());
+
+        let proof = v1.generate_membership_proof(&path).unwrap();
+        assert!(proof.verify_with(HashScheme::V1));
+        assert!(
+            !proof.verify(),
+            "V1 proof must not verify under the legacy scheme"
+        );
+
+        let revoked = HashScheme::V1.hash_leaf(b"revoked");
+        let exclusion = v1.generate_non_membership_proof(&revoked).unwrap();
+        assert!(exclusion.verify_with(HashScheme::V1));
+    }
+
+    #[test]
+    fn test_non_membership_proof() {
+        let mut tree = SparseQuadTree::new(8);
+        let member = hash_leaf(b"member");
+        let revoked = hash_leaf(b"revoked");
+        tree.insert_key(member).unwrap();
+
+        let proof = tree.generate_non_membership_proof(&revoked).unwrap();
+        assert_eq!(proof.root_hash, tree.root());
+        assert!(proof.verify());
+
+        assert!(tree.generate_non_membership_proof(&member).is_none());
+    }
+
+    #[test]
+    fn test_non_membership_proof_rejects_occupied_slot() {
+        let mut tree = SparseQuadTree::new(8);
+        let revoked = hash_leaf(b"revoked");
+        let proof = tree.generate_non_membership_proof(&revoked).unwrap();
+
+        tree.insert_key(revoked).unwrap();
+        let stale = QuadTreeNonMembershipProof {
+            root_hash: tree.root(),
+            ..proof.clone()
+        };
+        assert!(!stale.verify());
+
+        // Slot must be the one derived from the key hash
+        let mut moved = proof;
+        moved.leaf_index.path[0] ^= 1;
+        assert!(!moved.verify());
+    }
+
+    #[test]
+    fn test_insert_key_rejects_colliding_slot() {
+        // Both keys start with bits 00, so they share slot [0] at depth 1
+        let mut tree = SparseQuadTree::new(1);
+        let member = [0x02; 32];
+        let colliding = [0x01; 32];
+        let root = tree.insert_key(member).unwrap();
+
+        assert_eq!(tree.insert_key(colliding), None);
+        assert_eq!(tree.root(), root);
+        assert_eq!(tree.leaf_hash(&[0]), member);
+        assert_eq!(tree.insert_key(member), Some(root));
+
+        // The colliding key is absent, but its slot is not empty
+        assert!(tree.generate_non_membership_proof(&colliding).is_none());
+    }
+
+    #[test]
+    fn test_from_leaves_pads_to_power_of_four() {
+        assert_eq!(depth_for_leaf_count(0), 1);
+        assert_eq!(depth_for_leaf_count(4), 1);
+        assert_eq!(depth_for_leaf_count(5), 2);
+        assert_eq!(depth_for_leaf_count(64), 3);
+
+        let leaves = [b"alice".as_slice(), b"bob", b"carol", b"dave", b"erin"];
+        let tree = SparseQuadTree::from_leaves(leaves);
+        assert_eq!(tree.depth(), 2);
+        assert_eq!(tree.len(), 5);
+
+        let mut expected = SparseQuadTree::new(2);
+        for (i, leaf) in leaves.iter().enumerate() {
+            expected.insert(&[i as u8 / 4, i as u8 % 4], hash_leaf(leaf));
+        }
+        assert_eq!(tree.root(), expected.root());
+
+        assert!(tree.generate_membership_proof(&[1, 0]).unwrap().verify());
+        assert_eq!(tree.leaf_hash(&[1, 1]), EMPTY_LEAF_HASH);
+    }
+}
@@ -0,0 +1,224 @@
+use alloc::vec::Vec;
+
+use crate::{depth_for_leaf_count, HashScheme, QuadHasher, EMPTY_LEAF_HASH};
+
+/// Receives every node hash a `StreamingRootBuilder` completes
+///
+/// `height` is the level above the leaves (0 = leaf) and `index` counts nodes
+/// left to right within that level. Each level's nodes arrive in index order,
+/// though levels interleave. Padding is reported only inside groups that hold
+/// a real node; entirely empty groups to their right are left out.
+pub trait LevelSink {
+    fn node(&mut self, height: u8, index: u64, hash: &[u8; 32]);
+}
+
+/// Discards every node
+impl LevelSink for () {
+    fn node(&mut self, _height: u8, _index: u64, _hash: &[u8; 32]) {}
+}
+
+/// `None` discards every node
+impl<S: LevelSink> LevelSink for Option<S> {
+    fn node(&mut self, height: u8, index: u64, hash: &[u8; 32]) {
+        if let Some(sink) = self {
+            sink.node(height, index, hash)
+        }
+    }
+}
+
+impl<S: LevelSink + ?Sized> LevelSink for &mut S {
+    fn node(&mut self, height: u8, index: u64, hash: &[u8; 32]) {
+        (**self).node(height, index, hash)
+    }
+}
+
+/// Root of a tree over leaves supplied one at a time, left to right
+///
+/// Gives the same root as `SparseQuadTree::from_leaf_hashes` over the same
+/// leaves, without knowing the leaf count up front. Only a stack of up to 4
+/// pending hashes per level is kept, so memory is O(depth) however many leaves
+/// stream through.
+#[derive(Debug, Clone)]
+pub struct StreamingRootBuilder<H: QuadHasher = HashScheme, S: LevelSink = ()> {
+    hasher: H,
+    sink: S,
+    leaf_count: u64,
+    /// Completed nodes per height not yet folded into their parent
+    pending: Vec<Vec<[u8; 32]>>,
+    /// Nodes completed so far per height, i.e. the index of the next one
+    completed: Vec<u64>,
+}
+
+impl StreamingRootBuilder {
+    /// Builder with legacy SHA3-256 hashing
+    pub fn new() -> Self {
+        Self::with_hasher(HashScheme::Legacy)
+    }
+}
+
+impl Default for StreamingRootBuilder {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl<H: QuadHasher> StreamingRootBuilder<H> {
+    pub fn with_hasher(hasher: H) -> Self {
+        Self::with_sink(hasher, ())
+    }
+}
+
+impl<H: QuadHasher, S: LevelSink> StreamingRootBuilder<H, S> {
+    /// Builder that also reports every completed node to `sink`
+    /// (e.g. to write each level to its own file)
+    pub fn with_sink(hasher: H, sink: S) -> Self {
+        Self {
+            hasher,
+            sink,
+            leaf_count: 0,
+            pending: Vec::new(),
+            completed: Vec::new(),
+        }
+    }
+
+    /// Number of leaves pushed so far
+    pub fn leaf_count(&self) -> u64 {
+        self.leaf_count
+    }
+
+    /// Depth of the tree if it were finished now
+    pub fn depth(&self) -> u8 {
+        depth_for_leaf_count(self.leaf_count)
+    }
+
+    /// Hash a leaf value with `hash_leaf` and append it
+    pub fn push_leaf(&mut self, data: &[u8]) {
+        self.push_leaf_hash(self.hasher.hash_leaf(data));
+    }
+
+    /// Append an already hashed leaf
+    pub fn push_leaf_hash(&mut self, leaf_hash: [u8; 32]) {
+        assert!(self.leaf_count < u64::MAX, "Leaf count must fit in a u64");
+        self.push_node(0, leaf_hash);
+        self.leaf_count += 1;
+    }
+
+    /// Pad the last group of every level with empty subtrees and return the root
+    pub fn finish(self) -> [u8; 32] {
+        self.finish_with_sink().0
+    }
+
+    /// `finish`, also handing back the sink (e.g. to flush files it wrote)
+    pub fn finish_with_sink(mut self) -> ([u8; 32], S) {
+        let depth = self.depth() as usize;
+        let mut empty = EMPTY_LEAF_HASH;
+
+        for height in 0..depth {
+            // A partial group is folded as soon as padding fills it
+            while self
+                .pending
+                .get(height)
+                .is_some_and(|level| !level.is_empty())
+            {
+                self.push_node(height, empty);
+            }
+            empty = self
+                .hasher
+                .hash_node(height as u8 + 1, &empty, &empty, &empty, &empty);
+        }
+
+        // At most one node reaches the top level; none means no leaves were pushed
+        let root = match self.pending.get(depth).and_then(|level| level.first()) {
+            Some(root) => *root,
+            None => empty,
+        };
+        (root, self.sink)
+    }
+
+    /// Record a completed node and fold its group into the parent once full
+    fn push_node(&mut self, height: usize, hash: [u8; 32]) {
+        if self.pending.len() == height {
+            self.pending.push(Vec::with_capacity(4));
+            self.completed.push(0);
+        }
+        self.sink.node(height as u8, self.completed[height], &hash);
+        self.completed[height] += 1;
+
+        let level = &mut self.pending[height];
+        level.push(hash);
+        if level.len() == 4 {
+            let parent =
+                self.hasher
+                    .hash_node(height as u8 + 1, &level[0], &level[1], &level[2], &level[3]);
+            level.clear();
+            self.push_node(height + 1, parent);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{hash_leaf, QuadTreeIndex, SparseQuadTree};
+
+    struct Recorder(Vec<(u8, u64, [u8; 32])>);
+
+    impl LevelSink for Recorder {
+        fn node(&mut self, height: u8, index: u64, hash: &[u8; 32]) {
+            self.0.push((height, index, *hash));
+        }
+    }
+
+    fn leaves(count: u8) -> Vec<[u8; 32]> {
+        (0..count).map(|i| hash_leaf(&[i])).collect()
+    }
+
+    #[test]
+    fn test_streaming_root_matches_sparse_tree() {
+        for count in [0, 1, 3, 4, 5, 16, 17, 70] {
+            let mut builder = StreamingRootBuilder::new();
+            for leaf in leaves(count) {
+                builder.push_leaf_hash(leaf);
+            }
+            let expected = SparseQuadTree::from_leaf_hashes(leaves(count));
+            assert_eq!(builder.depth(), expected.depth());
+            assert_eq!(builder.finish(), expected.root(), "{} leaves", count);
+        }
+
+        let mut v1 = StreamingRootBuilder::with_hasher(HashScheme::V1);
+        for i in 0..7u8 {
+            v1.push_leaf(&[i]);
+        }
+        let expected = SparseQuadTree::from_leaves_with((0..7u8).map(|i| [i]), HashScheme::V1);
+        assert_eq!(v1.finish(), expected.root());
+    }
+
+    #[test]
+    fn test_level_sink_receives_every_level() {
+        let mut recorder = Recorder(Vec::new());
+        let mut builder = StreamingRootBuilder::with_sink(HashScheme::Legacy, &mut recorder);
+        for leaf in leaves(17) {
+            builder.push_leaf_hash(leaf);
+        }
+        let root = builder.finish();
+        let tree = SparseQuadTree::from_leaf_hashes(leaves(17));
+
+        // 17 leaves + 3 padding, 5 parents + 3 padding, 2 + 2 padding, root
+        let per_height: Vec<usize> = (0..4)
+            .map(|height| recorder.0.iter().filter(|node| node.0 == height).count())
+            .collect();
+        assert_eq!(per_height, [20, 8, 4, 1]);
+        assert_eq!(recorder.0.last().unwrap().2, root);
+
+        for &(height, index, hash) in &recorder.0 {
+            let node = QuadTreeIndex::from_leaf_number(index, tree.depth() - height);
+            assert_eq!(
+                hash,
+                tree.node_hash(&node),
+                "height {} index {}",
+                height,
+                index
+            );
+        }
+    }
+}
@@ -0,0 +1,21 @@
+#![no_main]
+#![no_std]
+
+use pico_sdk::io::{commit, read_as};
+use quad_tree_core::QuadTreeMultiProof;
+
+pico_sdk::entrypoint!(main);
+
+/// Verifies many memberships against one root in a single zkVM run
+pub fn main() {
+    let proof: QuadTreeMultiProof = read_as();
+    let is_valid = proof.verify();
+    let leaf_count = proof.leaves.len() as u32;
+    commit(&proof.root_hash);
+    commit(&leaf_count);
+    commit(&is_valid);
+
+    if !is_valid {
+        panic!("Invalid quaternary tree multi-proof");
+    }
+}
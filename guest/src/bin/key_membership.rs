@@ -0,0 +1,20 @@
+#![no_main]
+#![no_std]
+
+use pico_sdk::io::{commit, read_as};
+use quad_tree_core::QuadTreeKeyMembershipProof;
+
+pico_sdk::entrypoint!(main);
+
+/// Proves knowledge of a well-formed ML-KEM-768 key whose hash is a leaf
+/// The key itself stays private; only the root and validity are committed
+pub fn main() {
+    let proof: QuadTreeKeyMembershipProof = read_as();
+    let is_valid = proof.verify();
+    commit(&proof.root_hash);
+    commit(&is_valid);
+
+    if !is_valid {
+        panic!("Invalid quaternary tree key membership proof");
+    }
+}
@@ -0,0 +1,19 @@
+#![no_main]
+#![no_std]
+
+use pico_sdk::io::{commit, read_as};
+use quad_tree_core::{HashScheme, QuadTreeMembershipProof};
+
+pico_sdk::entrypoint!(main);
+
+/// Same as `quad-guest`, for trees built with `HashScheme::V1`
+pub fn main() {
+    let proof: QuadTreeMembershipProof = read_as();
+    let is_valid = proof.verify_with(HashScheme::V1);
+    commit(&proof.root_hash);
+    commit(&is_valid);
+
+    if !is_valid {
+        panic!("Invalid quaternary tree membership proof");
+    }
+}
@@ -0,0 +1,20 @@
+#![no_main]
+#![no_std]
+
+use pico_sdk::io::{commit, read_as};
+use quad_tree_core::QuadTreeNonMembershipProof;
+
+pico_sdk::entrypoint!(main);
+
+/// Proves inside the Pico zkVM that a key is NOT in the tree
+pub fn main() {
+    let proof: QuadTreeNonMembershipProof = read_as();
+    let is_valid = proof.verify();
+    commit(&proof.root_hash);
+    commit(&proof.key_hash);
+    commit(&is_valid);
+
+    if !is_valid {
+        panic!("Invalid quaternary tree non-membership proof");
+    }
+}
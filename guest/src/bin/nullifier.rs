@@ -0,0 +1,24 @@
+#![no_main]
+#![no_std]
+
+use pico_sdk::io::{commit, read_as};
+use quad_tree_core::QuadTreeNullifierProof;
+
+pico_sdk::entrypoint!(main);
+
+/// Anonymous membership: commits a nullifier instead of anything leaf-specific
+/// The leaf is recomputed from the secret (its identity commitment), so each
+/// member has exactly one nullifier per external nullifier
+/// The leaf hash, path and secret stay private
+pub fn main() {
+    let proof: QuadTreeNullifierProof = read_as();
+    let is_valid = proof.verify();
+    commit(&proof.root_hash());
+    commit(&proof.nullifier());
+    commit(&proof.external_nullifier);
+    commit(&is_valid);
+
+    if !is_valid {
+        panic!("Invalid quaternary tree membership proof");
+    }
+}
@@ -0,0 +1,20 @@
+#![no_main]
+#![no_std]
+
+use pico_sdk::io::{commit, read_as};
+use quad_tree_core::{HashScheme, Poseidon2Hasher, QuadTreeMembershipProof};
+
+pico_sdk::entrypoint!(main);
+
+/// Same as `quad-guest`, for trees hashed with Poseidon2 over KoalaBear
+pub fn main() {
+    let proof: QuadTreeMembershipProof = read_as();
+    let permutation = Poseidon2Hasher::permutation();
+    let is_valid = proof.verify_with(Poseidon2Hasher::new(&permutation, HashScheme::Legacy));
+    commit(&proof.root_hash);
+    commit(&is_valid);
+
+    if !is_valid {
+        panic!("Invalid quaternary tree membership proof");
+    }
+}
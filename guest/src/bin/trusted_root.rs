@@ -0,0 +1,21 @@
+#![no_main]
+#![no_std]
+
+use pico_sdk::io::{commit, read_as};
+use quad_tree_core::QuadTreeMembershipProof;
+
+pico_sdk::entrypoint!(main);
+
+/// Membership against a root supplied by the verifier, not taken from the proof
+/// Input is the bincode tuple (trusted_root, proof); only the trusted root is committed
+pub fn main() {
+    let (trusted_root, proof): ([u8; 32], QuadTreeMembershipProof) = read_as();
+    commit(&trusted_root);
+
+    let is_valid = proof.verify_against(&trusted_root);
+    commit(&is_valid);
+
+    if !is_valid {
+        panic!("Proof does not reconstruct the trusted root");
+    }
+}
@@ -0,0 +1,758 @@
+//! `quad-host` subcommands for scripting: build or edit a tree, prove a leaf,
+//! verify or inspect a proof, print a root
+//!
+//! Results go to stdout and progress to stderr. Exit status is 0 on success,
+//! 1 if a proof fails verification and 2 for bad arguments or unreadable input.
+
+use std::fmt;
+use std::fs::File;
+use std::io::{BufRead, BufReader, BufWriter, Write};
+use std::path::{Path, PathBuf};
+use std::process::ExitCode;
+
+use clap::{Parser, Subcommand, ValueEnum};
+use quad_tree_core::{
+    HashScheme, LevelSink, QuadTreeIndex, QuadTreeMembershipProof, StreamingRootBuilder,
+    VerifyError,
+};
+use serde::{Deserialize, Serialize};
+
+use crate::keystore::KeyStore;
+use crate::store::{NodeStore, StoreError, StoreWriter};
+use crate::{
+    build_from_leaf_level, build_quad_tree_from_leaf_hashes, build_quad_tree_from_leaves,
+    build_quad_tree_parallel, generate_membership_proof, read_proof_file, QuadTree,
+};
+
+#[derive(Parser)]
+#[command(
+    name = "quad-host",
+    about = "Quaternary Merkle tree prover for the Pico zkVM"
+)]
+pub(crate) struct Cli {
+    /// Without a subcommand, runs the end-to-end demo
+    #[command(subcommand)]
+    pub(crate) command: Option<Command>,
+}
+
+#[derive(Subcommand)]
+pub(crate) enum Command {
+    /// Build a tree and save it to a tree file
+    Build {
+        /// Generate 4^depth fresh ML-KEM-768 keys on all cores (ignored with --leaves)
+        #[arg(long, default_value_t = 3)]
+        depth: u8,
+        /// Hex leaf values, one per line, instead of generated keys
+        #[arg(long)]
+        leaves: Option<PathBuf>,
+        /// Treat --leaves lines as 32-byte leaf hashes rather than leaf data
+        #[arg(long, requires = "leaves")]
+        hashed: bool,
+        /// 32-byte hex master seed for reproducible key generation
+        #[arg(long, conflicts_with = "leaves")]
+        seed: Option<String>,
+        /// Save decapsulation keys here, encrypted with $QUAD_KEYSTORE_PASSPHRASE
+        #[arg(long, conflicts_with = "leaves")]
+        keystore: Option<PathBuf>,
+        /// `.qtns` saves a node store that later commands read lazily, anything else JSON
+        #[arg(long, short, default_value = "quad_tree.json")]
+        out: PathBuf,
+    },
+    /// Put a leaf into an empty slot of a JSON tree file and print the new root
+    Insert {
+        #[arg(long, default_value = "quad_tree.json")]
+        tree: PathBuf,
+        /// Leaf path as digits 0-3 from the root
+        #[arg(long)]
+        path: String,
+        /// Hex leaf value, e.g. an ML-KEM encapsulation key
+        #[arg(long)]
+        leaf: String,
+        /// Treat --leaf as a 32-byte leaf hash rather than leaf data
+        #[arg(long)]
+        hashed: bool,
+    },
+    /// Replace an occupied leaf of a JSON tree file (e.g. rotate its key) and print the new root
+    Update {
+        #[arg(long, default_value = "quad_tree.json")]
+        tree: PathBuf,
+        /// Leaf path as digits 0-3 from the root
+        #[arg(long)]
+        path: String,
+        /// Hex leaf value, e.g. an ML-KEM encapsulation key
+        #[arg(long)]
+        leaf: String,
+        /// Treat --leaf as a 32-byte leaf hash rather than leaf data
+        #[arg(long)]
+        hashed: bool,
+    },
+    /// Empty an occupied leaf of a JSON tree file and print the new root
+    Remove {
+        #[arg(long, default_value = "quad_tree.json")]
+        tree: PathBuf,
+        /// Leaf path as digits 0-3 from the root
+        #[arg(long)]
+        path: String,
+    },
+    /// Generate a membership proof for one leaf
+    Prove {
+        #[arg(long, default_value = "quad_tree.json")]
+        tree: PathBuf,
+        /// Leaf path as digits 0-3 from the root, e.g. 012 or 0,1,2
+        #[arg(long, conflicts_with = "index", required_unless_present = "index")]
+        path: Option<String>,
+        /// Leaf number, left to right
+        #[arg(long)]
+        index: Option<u64>,
+        #[arg(long, short, default_value = "quad_proof.bin")]
+        out: PathBuf,
+        /// Defaults to the output file's extension (.json, .qtmp, else bincode)
+        #[arg(long)]
+        format: Option<ProofFormat>,
+    },
+    /// Verify a proof; exits with status 1 if it is invalid
+    Verify {
+        proof: PathBuf,
+        /// Hex root to verify against instead of the root inside the proof
+        #[arg(long)]
+        root: Option<String>,
+    },
+    /// Print a proof's fields
+    Inspect { proof: PathBuf },
+    /// Print a tree file's root hash, or stream a leaf file to its root
+    Root {
+        #[arg(long, default_value = "quad_tree.json")]
+        tree: PathBuf,
+        /// Hex leaf values, one per line, hashed as they are read without building the tree
+        #[arg(long, conflicts_with = "tree")]
+        leaves: Option<PathBuf>,
+        /// Treat --leaves lines as 32-byte leaf hashes rather than leaf data
+        #[arg(long, requires = "leaves", conflicts_with = "tree")]
+        hashed: bool,
+        /// Also write each level's hashes to DIR/level_<height>.bin (0 = leaves)
+        #[arg(long, requires = "leaves", conflicts_with = "tree")]
+        levels: Option<PathBuf>,
+        /// Also save the tree as a node store (.qtns); reads --leaves twice, to count then hash
+        #[arg(long, requires = "leaves", conflicts_with_all = ["tree", "levels"])]
+        store: Option<PathBuf>,
+    },
+}
+
+#[derive(Clone, Copy, PartialEq, Eq, Debug, ValueEnum)]
+pub(crate) enum ProofFormat {
+    /// Human-readable JSON with hex hashes
+    Json,
+    /// Bincode, the input of quad-guest
+    Bin,
+    /// Versioned binary wire format
+    Qtmp,
+    /// Bincode (root, proof) pair, the input of quad-guest-trusted-root
+    TrustedRoot,
+}
+
+impl ProofFormat {
+    fn from_extension(file: &Path) -> Self {
+        match file.extension().and_then(|ext| ext.to_str()) {
+            Some("json") => Self::Json,
+            Some("qtmp") => Self::Qtmp,
+            _ => Self::Bin,
+        }
+    }
+}
+
+#[derive(Debug)]
+pub(crate) enum CliError {
+    /// Bad arguments, unreadable files or malformed contents
+    Input(String),
+    InvalidProof(VerifyError),
+}
+
+impl CliError {
+    pub(crate) fn exit_code(&self) -> ExitCode {
+        match self {
+            Self::InvalidProof(_) => ExitCode::from(1),
+            Self::Input(_) => ExitCode::from(2),
+        }
+    }
+}
+
+impl fmt::Display for CliError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::Input(msg) => write!(f, "{}", msg),
+            Self::InvalidProof(err) => write!(f, "invalid proof: {}", err),
+        }
+    }
+}
+
+fn input_error(context: impl fmt::Display, err: impl fmt::Display) -> CliError {
+    CliError::Input(format!("{}: {}", context, err))
+}
+
+/// Saved tree: every leaf slot left to right, padding included
+#[derive(Serialize, Deserialize)]
+struct TreeFile {
+    depth: u8,
+    root: String,
+    leaves: Vec<StoredLeaf>,
+}
+
+#[derive(Serialize, Deserialize)]
+struct StoredLeaf {
+    hash: String,
+    /// Leaf preimage (e.g. the ML-KEM encapsulation key), if known
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    data: Option<String>,
+}
+
+/// `--leaf` of insert and update, or a line of a streamed leaf file: a value to
+/// hash, or with `--hashed` the hash itself
+enum LeafArg {
+    Data(Vec<u8>),
+    Hash([u8; 32]),
+}
+
+impl LeafArg {
+    fn parse(value: &str, hashed: bool) -> Result<Self, CliError> {
+        if hashed {
+            return parse_hex_32(value, "--leaf").map(Self::Hash);
+        }
+        hex::decode(value.trim())
+            .map(Self::Data)
+            .map_err(|err| input_error("--leaf", err))
+    }
+}
+
+pub(crate) fn run(command: Command) -> Result<(), CliError> {
+    match command {
+        Command::Build {
+            depth,
+            leaves,
+            hashed,
+            seed,
+            keystore,
+            out,
+        } => {
+            let tree = match leaves {
+                Some(file) => {
+                    let values = read_leaf_file(&file)?;
+                    if hashed {
+                        let hashes = values
+                            .into_iter()
+                            .map(|value| {
+                                value.try_into().map_err(|_| {
+                                    CliError::Input("--hashed leaves must be 32 bytes".to_string())
+                                })
+                            })
+                            .collect::<Result<Vec<[u8; 32]>, _>>()?;
+                        build_quad_tree_from_leaf_hashes(hashes)
+                    } else {
+                        build_quad_tree_from_leaves(values)
+                    }
+                }
+                None => build_generated(depth, seed.as_deref(), keystore.as_deref())?,
+            };
+            save_tree(&tree, &out)?;
+            eprintln!("Saved tree to {}", out.display());
+            println!("{}", hex::encode(tree.root()));
+        }
+        Command::Insert {
+            tree,
+            path,
+            leaf,
+            hashed,
+        } => {
+            let leaf = LeafArg::parse(&leaf, hashed)?;
+            let root = edit_tree(
+                &tree,
+                &path,
+                "is already occupied",
+                |tree, path| match leaf {
+                    LeafArg::Data(data) => tree.insert_leaf(path, data),
+                    LeafArg::Hash(hash) => tree.insert(path, hash),
+                },
+            )?;
+            println!("{}", hex::encode(root));
+        }
+        Command::Update {
+            tree,
+            path,
+            leaf,
+            hashed,
+        } => {
+            let leaf = LeafArg::parse(&leaf, hashed)?;
+            let root = edit_tree(&tree, &path, "is empty", |tree, path| match leaf {
+                LeafArg::Data(data) => tree.update_leaf(path, data),
+                LeafArg::Hash(hash) => tree.update(path, hash),
+            })?;
+            println!("{}", hex::encode(root));
+        }
+        Command::Remove { tree, path } => {
+            let root = edit_tree(&tree, &path, "is already empty", |tree, path| {
+                tree.remove(path)
+            })?;
+            println!("{}", hex::encode(root));
+        }
+        Command::Prove {
+            tree,
+            path,
+            index,
+            out,
+            format,
+        } => {
+            let tree = SavedTree::open(&tree)?;
+            let depth = tree.depth();
+            let leaf_path = match (path, index) {
+                (Some(path), _) => parse_path(&path)?,
+                (None, Some(index)) => {
+                    if depth < 32 && index >= 1u64 << (2 * depth as u32) {
+                        return Err(CliError::Input(format!(
+                            "leaf index {} out of range for depth {}",
+                            index, depth
+                        )));
+                    }
+                    QuadTreeIndex::from_leaf_number(index, depth).path
+                }
+                (None, None) => unreachable!("clap requires --path or --index"),
+            };
+            check_path_depth(&leaf_path, depth)?;
+
+            let proof = tree.prove(&leaf_path)?;
+            let format = format.unwrap_or_else(|| ProofFormat::from_extension(&out));
+            write_proof(&proof, format, &out)?;
+            eprintln!("Saved {:?} proof to {}", format, out.display());
+        }
+        Command::Verify { proof, root } => {
+            let proof = read_proof_file(&proof).map_err(|err| input_error(proof.display(), err))?;
+            let result = match root {
+                Some(root) => {
+                    proof.verify_detailed_against(&parse_hash(&root)?, HashScheme::Legacy)
+                }
+                None => proof.verify_detailed(),
+            };
+            result.map_err(CliError::InvalidProof)?;
+            println!("valid");
+        }
+        Command::Inspect { proof: file } => {
+            let proof = read_proof_file(&file).map_err(|err| input_error(file.display(), err))?;
+            let index = &proof.leaf_index;
+            let digits: String = index.path.iter().map(|branch| branch.to_string()).collect();
+            println!("depth:          {}", index.depth);
+            println!("path:           {}", digits);
+            if index.depth <= 32 {
+                println!("leaf number:    {}", index.leaf_number());
+            }
+            println!("leaf hash:      {}", hex::encode(proof.leaf_hash));
+            println!("root hash:      {}", hex::encode(proof.root_hash));
+            println!("sibling levels: {}", proof.sibling_hashes.len());
+            println!("wire size:      {} bytes", proof.size_bytes());
+            match proof.verify_detailed() {
+                Ok(()) => println!("self-consistent: yes"),
+                Err(err) => println!("self-consistent: no ({})", err),
+            }
+        }
+        Command::Root {
+            tree,
+            leaves,
+            hashed,
+            levels,
+            store,
+        } => {
+            let root = match (leaves, store) {
+                (Some(file), Some(store)) => stream_store(&file, hashed, &store)?,
+                (Some(file), None) => stream_root(&file, hashed, levels.as_deref())?,
+                (None, _) => SavedTree::open(&tree)?.root()?,
+            };
+            println!("{}", hex::encode(root));
+        }
+    }
+    Ok(())
+}
+
+fn check_path_depth(leaf_path: &[u8], depth: u8) -> Result<(), CliError> {
+    if leaf_path.len() != depth as usize {
+        return Err(CliError::Input(format!(
+            "leaf path has {} levels but the tree depth is {}",
+            leaf_path.len(),
+            depth
+        )));
+    }
+    Ok(())
+}
+
+/// Apply one registry operation to a JSON tree file, save it and return the new root
+/// `edit` returns `None` when the slot is not in the state the operation needs,
+/// reported as "leaf <path> <refusal>"
+fn edit_tree(
+    file: &Path,
+    path: &str,
+    refusal: &str,
+    edit: impl FnOnce(&mut QuadTree, &[u8]) -> Option<[u8; 32]>,
+) -> Result<[u8; 32], CliError> {
+    if is_node_store(file) {
+        return Err(CliError::Input(format!(
+            "{}: node stores are read-only, edit the JSON tree file",
+            file.display()
+        )));
+    }
+    let mut tree = load_tree(file)?;
+    let leaf_path = parse_path(path)?;
+    check_path_depth(&leaf_path, tree.depth())?;
+    let root = edit(&mut tree, &leaf_path)
+        .ok_or_else(|| CliError::Input(format!("leaf {} {}", path, refusal)))?;
+    save_tree(&tree, file)?;
+    eprintln!("Saved tree to {}", file.display());
+    Ok(root)
+}
+
+fn build_generated(
+    depth: u8,
+    seed: Option<&str>,
+    keystore_file: Option<&Path>,
+) -> Result<QuadTree, CliError> {
+    if !(1..=8).contains(&depth) {
+        return Err(CliError::Input(
+            "--depth must be 1-8 when generating keys".to_string(),
+        ));
+    }
+    let master_seed = seed.map(parse_seed).transpose()?;
+
+    let Some(keystore_file) = keystore_file else {
+        return Ok(build_quad_tree_parallel(depth, master_seed.as_ref(), None));
+    };
+    let passphrase = std::env::var("QUAD_KEYSTORE_PASSPHRASE").map_err(|_| {
+        CliError::Input("--keystore needs QUAD_KEYSTORE_PASSPHRASE to be set".to_string())
+    })?;
+    let mut keystore = KeyStore::new(&passphrase).map_err(|err| input_error("keystore", err))?;
+    let tree = build_quad_tree_parallel(depth, master_seed.as_ref(), Some(&mut keystore));
+    keystore
+        .save(keystore_file)
+        .map_err(|err| input_error(keystore_file.display(), err))?;
+    eprintln!(
+        "Saved {} decapsulation keys to {}",
+        keystore.len(),
+        keystore_file.display()
+    );
+    Ok(tree)
+}
+
+/// Hex values, one per line; blank lines and lines starting with `#` are skipped
+fn read_leaf_file(file: &Path) -> Result<Vec<Vec<u8>>, CliError> {
+    let contents = std::fs::read_to_string(file).map_err(|err| input_error(file.display(), err))?;
+    contents
+        .lines()
+        .enumerate()
+        .filter_map(|(number, line)| parse_leaf_line(file, number, line))
+        .collect()
+}
+
+/// Decode line `number` (0-based) of a leaf file, or `None` to skip it
+fn parse_leaf_line(file: &Path, number: usize, line: &str) -> Option<Result<Vec<u8>, CliError>> {
+    let line = line.trim();
+    if line.is_empty() || line.starts_with('#') {
+        return None;
+    }
+    Some(
+        hex::decode(line)
+            .map_err(|err| input_error(format!("{}:{}", file.display(), number + 1), err)),
+    )
+}
+
+/// Call `push` with each leaf of a leaf file, read line by line
+fn for_each_leaf(
+    file: &Path,
+    hashed: bool,
+    mut push: impl FnMut(LeafArg) -> Result<(), CliError>,
+) -> Result<(), CliError> {
+    let reader = BufReader::new(File::open(file).map_err(|err| input_error(file.display(), err))?);
+    for (number, line) in reader.lines().enumerate() {
+        let line = line.map_err(|err| input_error(file.display(), err))?;
+        let Some(value) = parse_leaf_line(file, number, &line).transpose()? else {
+            continue;
+        };
+        let leaf = if hashed {
+            LeafArg::Hash(
+                value
+                    .try_into()
+                    .map_err(|_| CliError::Input("--hashed leaves must be 32 bytes".to_string()))?,
+            )
+        } else {
+            LeafArg::Data(value)
+        };
+        push(leaf)?;
+    }
+    Ok(())
+}
+
+/// Root over a leaf file read line by line, holding O(depth) hashes
+pub(crate) fn stream_root(
+    file: &Path,
+    hashed: bool,
+    level_dir: Option<&Path>,
+) -> Result<[u8; 32], CliError> {
+    let mut level_files = level_dir.map(LevelFiles::new).transpose()?;
+    let mut builder = StreamingRootBuilder::with_sink(HashScheme::Legacy, &mut level_files);
+    for_each_leaf(file, hashed, |leaf| {
+        match leaf {
+            LeafArg::Data(data) => builder.push_leaf(&data),
+            LeafArg::Hash(hash) => builder.push_leaf_hash(hash),
+        }
+        Ok(())
+    })?;
+    let leaf_count = builder.leaf_count();
+    let depth = builder.depth();
+    let root = builder.finish();
+
+    eprintln!("Hashed {} leaves (depth {})", leaf_count, depth);
+    if let (Some(level_files), Some(dir)) = (level_files, level_dir) {
+        let count = level_files
+            .finish()
+            .map_err(|err| input_error(dir.display(), err))?;
+        eprintln!("Saved {} level files to {}", count, dir.display());
+    }
+    Ok(root)
+}
+
+/// Node store over a leaf file, holding O(depth) hashes
+///
+/// A first pass counts the leaves, which fixes the depth and so every slot of
+/// the store; the second pass hashes them straight into the file.
+pub(crate) fn stream_store(file: &Path, hashed: bool, store: &Path) -> Result<[u8; 32], CliError> {
+    let mut leaf_count = 0u64;
+    for_each_leaf(file, hashed, |_| {
+        leaf_count += 1;
+        Ok(())
+    })?;
+
+    let store_error = |err: StoreError| input_error(store.display(), err);
+    let mut writer =
+        StoreWriter::create(store, leaf_count, HashScheme::Legacy).map_err(store_error)?;
+    let depth = writer.depth();
+    for_each_leaf(file, hashed, |leaf| {
+        match leaf {
+            LeafArg::Data(data) => writer.push_leaf(&data),
+            LeafArg::Hash(hash) => writer.push_leaf_hash(hash),
+        }
+        .map_err(store_error)
+    })?;
+    let root = writer.finish().map_err(store_error)?;
+
+    eprintln!("Hashed {} leaves (depth {})", leaf_count, depth);
+    eprintln!("Saved node store to {}", store.display());
+    Ok(root)
+}
+
+/// Appends each level's hashes, left to right, to `level_<height>.bin`
+struct LevelFiles {
+    dir: PathBuf,
+    files: Vec<BufWriter<File>>,
+    /// First write failure; `LevelSink` cannot return errors
+    error: Option<std::io::Error>,
+}
+
+impl LevelFiles {
+    fn new(dir: &Path) -> Result<Self, CliError> {
+        std::fs::create_dir_all(dir).map_err(|err| input_error(dir.display(), err))?;
+        Ok(Self {
+            dir: dir.to_path_buf(),
+            files: Vec::new(),
+            error: None,
+        })
+    }
+
+    fn write(&mut self, height: u8, hash: &[u8; 32]) -> std::io::Result<()> {
+        while self.files.len() <= height as usize {
+            let file = self.dir.join(format!("level_{}.bin", self.files.len()));
+            self.files.push(BufWriter::new(File::create(file)?));
+        }
+        self.files[height as usize].write_all(hash)
+    }
+
+    /// Flush every level file and return how many were written
+    fn finish(mut self) -> std::io::Result<usize> {
+        if let Some(err) = self.error.take() {
+            return Err(err);
+        }
+        for file in &mut self.files {
+            file.flush()?;
+        }
+        Ok(self.files.len())
+    }
+}
+
+impl LevelSink for LevelFiles {
+    fn node(&mut self, height: u8, _index: u64, hash: &[u8; 32]) {
+        // Nodes of one level arrive in index order, so appending keeps them in place
+        if self.error.is_none() {
+            self.error = self.write(height, hash).err();
+        }
+    }
+}
+
+/// Accepts `012`, `0,1,2` or `0 1 2`
+pub(crate) fn parse_path(path: &str) -> Result<Vec<u8>, CliError> {
+    path.chars()
+        .filter(|c| *c != ',' && !c.is_whitespace())
+        .map(|c| match c {
+            '0'..='3' => Ok(c as u8 - b'0'),
+            _ => Err(CliError::Input(format!(
+                "leaf path digits must be 0-3, got {:?}",
+                c
+            ))),
+        })
+        .collect()
+}
+
+fn parse_hash(value: &str) -> Result<[u8; 32], CliError> {
+    parse_hex_32(value, "hash")
+}
+
+fn parse_seed(value: &str) -> Result<[u8; 32], CliError> {
+    parse_hex_32(value, "--seed")
+}
+
+/// 64 hex digits; `what` names the value in errors
+fn parse_hex_32(value: &str, what: &str) -> Result<[u8; 32], CliError> {
+    hex::decode(value.trim())
+        .map_err(|err| input_error(what, err))?
+        .try_into()
+        .map_err(|_| CliError::Input(format!("{} must be 32 bytes (64 hex digits)", what)))
+}
+
+fn write_proof(
+    proof: &QuadTreeMembershipProof,
+    format: ProofFormat,
+    out: &Path,
+) -> Result<(), CliError> {
+    let bytes = match format {
+        ProofFormat::Json => {
+            serde_json::to_vec_pretty(proof).map_err(|err| input_error("json", err))?
+        }
+        ProofFormat::Bin => bincode::serialize(proof).map_err(|err| input_error("bincode", err))?,
+        ProofFormat::Qtmp => proof.to_bytes().map_err(|err| input_error("qtmp", err))?,
+        ProofFormat::TrustedRoot => bincode::serialize(&(proof.root_hash, proof))
+            .map_err(|err| input_error("bincode", err))?,
+    };
+    std::fs::write(out, bytes).map_err(|err| input_error(out.display(), err))
+}
+
+/// A tree file: JSON is rebuilt in memory, a `.qtns` node store stays on disk
+enum SavedTree {
+    Json(QuadTree),
+    Store(NodeStore),
+}
+
+impl SavedTree {
+    fn open(file: &Path) -> Result<Self, CliError> {
+        if is_node_store(file) {
+            let store = NodeStore::open(file).map_err(|err| input_error(file.display(), err))?;
+            Ok(Self::Store(store))
+        } else {
+            load_tree(file).map(Self::Json)
+        }
+    }
+
+    fn depth(&self) -> u8 {
+        match self {
+            Self::Json(tree) => tree.depth(),
+            Self::Store(store) => store.depth(),
+        }
+    }
+
+    fn root(&self) -> Result<[u8; 32], CliError> {
+        match self {
+            Self::Json(tree) => Ok(tree.root()),
+            Self::Store(store) => store.root().map_err(|err| input_error("node store", err)),
+        }
+    }
+
+    fn prove(&self, leaf_path: &[u8]) -> Result<QuadTreeMembershipProof, CliError> {
+        match self {
+            Self::Json(tree) => Ok(generate_membership_proof(tree, leaf_path)),
+            Self::Store(store) => store
+                .generate_membership_proof(leaf_path)
+                .map_err(|err| input_error("node store", err)),
+        }
+    }
+}
+
+fn is_node_store(file: &Path) -> bool {
+    file.extension().and_then(|ext| ext.to_str()) == Some("qtns")
+}
+
+pub(crate) fn save_tree(tree: &QuadTree, file: &Path) -> Result<(), CliError> {
+    if is_node_store(file) {
+        return NodeStore::save(tree, file).map_err(|err| input_error(file.display(), err));
+    }
+    let stored = TreeFile {
+        depth: tree.depth(),
+        root: hex::encode(tree.root()),
+        leaves: tree
+            .leaves()
+            .iter()
+            .enumerate()
+            .map(|(number, hash)| StoredLeaf {
+                hash: hex::encode(hash),
+                data: tree.leaf_data(number).map(hex::encode),
+            })
+            .collect(),
+    };
+    let json = serde_json::to_string(&stored).map_err(|err| input_error("tree", err))?;
+    std::fs::write(file, json).map_err(|err| input_error(file.display(), err))
+}
+
+/// Rebuild a tree saved by `save_tree`, checking every leaf's data against its
+/// hash and the leaves against the stored root
+pub(crate) fn load_tree(file: &Path) -> Result<QuadTree, CliError> {
+    let contents = std::fs::read_to_string(file).map_err(|err| input_error(file.display(), err))?;
+    let stored: TreeFile =
+        serde_json::from_str(&contents).map_err(|err| input_error(file.display(), err))?;
+
+    let slots = 4usize
+        .checked_pow(stored.depth as u32)
+        .filter(|_| stored.depth >= 1);
+    if slots != Some(stored.leaves.len()) {
+        return Err(CliError::Input(format!(
+            "{}: {} leaves do not fill a depth {} tree",
+            file.display(),
+            stored.leaves.len(),
+            stored.depth
+        )));
+    }
+
+    let hasher = HashScheme::Legacy;
+    let mut hashes = Vec::with_capacity(stored.leaves.len());
+    let mut leaf_data = Vec::with_capacity(stored.leaves.len());
+    for (number, leaf) in stored.leaves.iter().enumerate() {
+        let hash = parse_hash(&leaf.hash)?;
+        let data = leaf
+            .data
+            .as_deref()
+            .map(hex::decode)
+            .transpose()
+            .map_err(|err| input_error(file.display(), err))?;
+        if data
+            .as_ref()
+            .is_some_and(|data| hasher.hash_leaf(data) != hash)
+        {
+            return Err(CliError::Input(format!(
+                "{}: data of leaf {} does not hash to its stored hash",
+                file.display(),
+                number
+            )));
+        }
+        hashes.push(hash);
+        leaf_data.push(data);
+    }
+    let root =
+        parse_hex_32(&stored.root, "root").map_err(|err| input_error(file.display(), err))?;
+    let tree = build_from_leaf_level(hashes, leaf_data, hasher);
+
+    if tree.root() != root {
+        return Err(CliError::Input(format!(
+            "{}: leaves do not hash to the stored root",
+            file.display()
+        )));
+    }
+    Ok(tree)
+}
@@ -0,0 +1,259 @@
+//! Encrypted storage for the ML-KEM decapsulation keys behind a tree's leaves
+//!
+//! Each key is sealed with ChaCha20-Poly1305 under a key derived from a
+//! passphrase with Argon2id. The leaf path is bound in as associated data,
+//! so an entry moved to another path fails to decrypt.
+
+use std::collections::BTreeMap;
+use std::fmt;
+use std::path::Path;
+
+use argon2::Argon2;
+use chacha20poly1305::aead::{Aead, Payload};
+use chacha20poly1305::{ChaCha20Poly1305, KeyInit, Nonce};
+use ml_kem::kem::Decapsulate;
+use ml_kem::{Ciphertext, EncodedSizeUser, KemCore, MlKem768};
+use rand::rngs::OsRng;
+use rand::RngCore;
+use serde::{Deserialize, Serialize};
+
+pub(crate) type DecapsulationKey = <MlKem768 as KemCore>::DecapsulationKey;
+
+const KEYSTORE_VERSION: u32 = 1;
+const SALT_SIZE: usize = 16;
+const NONCE_SIZE: usize = 12;
+/// Associated data for `check`, which lets `load` reject a wrong passphrase up front
+const CHECK_AAD: &[u8] = b"QUAD_KEYSTORE_CHECK";
+
+#[derive(Debug)]
+pub(crate) enum KeyStoreError {
+    Io(std::io::Error),
+    Format(String),
+    /// The passphrase does not match, or the file was tampered with
+    Decryption,
+    UnknownLeaf(Vec<u8>),
+    Decapsulation,
+}
+
+impl fmt::Display for KeyStoreError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::Io(err) => write!(f, "keystore I/O error: {}", err),
+            Self::Format(msg) => write!(f, "malformed keystore: {}", msg),
+            Self::Decryption => write!(f, "wrong passphrase or corrupted keystore"),
+            Self::UnknownLeaf(path) => write!(f, "no key stored for leaf {:?}", path),
+            Self::Decapsulation => write!(f, "decapsulation failed"),
+        }
+    }
+}
+
+impl std::error::Error for KeyStoreError {}
+
+impl From<std::io::Error> for KeyStoreError {
+    fn from(err: std::io::Error) -> Self {
+        Self::Io(err)
+    }
+}
+
+/// On-disk layout; binary fields are hex
+#[derive(Serialize, Deserialize)]
+struct KeyStoreFile {
+    version: u32,
+    /// Argon2id (v19, default parameters) salt
+    salt: String,
+    /// Empty message sealed under the derived key with `CHECK_AAD`
+    check: EncryptedEntry,
+    entries: Vec<KeyStoreEntry>,
+}
+
+#[derive(Serialize, Deserialize)]
+struct KeyStoreEntry {
+    path: Vec<u8>,
+    #[serde(flatten)]
+    sealed: EncryptedEntry,
+}
+
+#[derive(Clone, Serialize, Deserialize)]
+struct EncryptedEntry {
+    nonce: String,
+    ciphertext: String,
+}
+
+/// Decapsulation keys indexed by leaf path, encrypted at rest
+pub(crate) struct KeyStore {
+    salt: [u8; SALT_SIZE],
+    cipher: ChaCha20Poly1305,
+    check: EncryptedEntry,
+    entries: BTreeMap<Vec<u8>, EncryptedEntry>,
+}
+
+impl KeyStore {
+    /// Create an empty keystore protected by `passphrase`
+    pub(crate) fn new(passphrase: &str) -> Result<Self, KeyStoreError> {
+        let mut salt = [0u8; SALT_SIZE];
+        OsRng.fill_bytes(&mut salt);
+        let cipher = derive_cipher(passphrase, &salt)?;
+        let check = seal(&cipher, &[], CHECK_AAD)?;
+
+        Ok(Self {
+            salt,
+            cipher,
+            check,
+            entries: BTreeMap::new(),
+        })
+    }
+
+    /// Read a keystore written by `save`
+    /// Fails with `KeyStoreError::Decryption` if the passphrase is wrong
+    pub(crate) fn load(file: impl AsRef<Path>, passphrase: &str) -> Result<Self, KeyStoreError> {
+        let contents = std::fs::read_to_string(file)?;
+        let stored: KeyStoreFile = serde_json::from_str(&contents)
+            .map_err(|err| KeyStoreError::Format(err.to_string()))?;
+        if stored.version != KEYSTORE_VERSION {
+            return Err(KeyStoreError::Format(format!(
+                "unsupported version {}",
+                stored.version
+            )));
+        }
+
+        let salt: [u8; SALT_SIZE] = decode_hex(&stored.salt)?
+            .try_into()
+            .map_err(|_| KeyStoreError::Format("salt must be 16 bytes".to_string()))?;
+        let cipher = derive_cipher(passphrase, &salt)?;
+        open(&cipher, &stored.check, CHECK_AAD)?;
+
+        Ok(Self {
+            salt,
+            cipher,
+            check: stored.check,
+            entries: stored
+                .entries
+                .into_iter()
+                .map(|entry| (entry.path, entry.sealed))
+                .collect(),
+        })
+    }
+
+    pub(crate) fn save(&self, file: impl AsRef<Path>) -> Result<(), KeyStoreError> {
+        let stored = KeyStoreFile {
+            version: KEYSTORE_VERSION,
+            salt: hex::encode(self.salt),
+            check: self.check.clone(),
+            entries: self
+                .entries
+                .iter()
+                .map(|(path, sealed)| KeyStoreEntry {
+                    path: path.clone(),
+                    sealed: sealed.clone(),
+                })
+                .collect(),
+        };
+        let json = serde_json::to_string_pretty(&stored)
+            .map_err(|err| KeyStoreError::Format(err.to_string()))?;
+        std::fs::write(file, json)?;
+        Ok(())
+    }
+
+    pub(crate) fn len(&self) -> usize {
+        self.entries.len()
+    }
+
+    /// Encrypt and store the decapsulation key for the leaf at `path`
+    /// Replaces any key already stored for that leaf
+    pub(crate) fn insert(
+        &mut self,
+        path: &[u8],
+        decapsulation_key: &DecapsulationKey,
+    ) -> Result<(), KeyStoreError> {
+        let sealed = seal(&self.cipher, &decapsulation_key.as_bytes(), path)?;
+        self.entries.insert(path.to_vec(), sealed);
+        Ok(())
+    }
+
+    /// Decrypt the decapsulation key for the leaf at `path`
+    pub(crate) fn decapsulation_key(&self, path: &[u8]) -> Result<DecapsulationKey, KeyStoreError> {
+        let sealed = self
+            .entries
+            .get(path)
+            .ok_or_else(|| KeyStoreError::UnknownLeaf(path.to_vec()))?;
+        let key_bytes = open(&self.cipher, sealed, path)?;
+        let encoded = key_bytes
+            .as_slice()
+            .try_into()
+            .map_err(|_| KeyStoreError::Format("wrong decapsulation key size".to_string()))?;
+        Ok(DecapsulationKey::from_bytes(&encoded))
+    }
+
+    /// Recover the shared secret a sender encapsulated to the leaf at `path`
+    pub(crate) fn decapsulate(
+        &self,
+        path: &[u8],
+        ciphertext: &[u8],
+    ) -> Result<[u8; 32], KeyStoreError> {
+        let decapsulation_key = self.decapsulation_key(path)?;
+        let ciphertext: Ciphertext<MlKem768> = ciphertext
+            .try_into()
+            .map_err(|_| KeyStoreError::Format("wrong ciphertext size".to_string()))?;
+        let shared_key = decapsulation_key
+            .decapsulate(&ciphertext)
+            .map_err(|_| KeyStoreError::Decapsulation)?;
+
+        let mut shared_secret = [0u8; 32];
+        shared_secret.copy_from_slice(&shared_key);
+        Ok(shared_secret)
+    }
+}
+
+fn derive_cipher(
+    passphrase: &str,
+    salt: &[u8; SALT_SIZE],
+) -> Result<ChaCha20Poly1305, KeyStoreError> {
+    let mut key = [0u8; 32];
+    Argon2::default()
+        .hash_password_into(passphrase.as_bytes(), salt, &mut key)
+        .map_err(|err| KeyStoreError::Format(err.to_string()))?;
+    Ok(ChaCha20Poly1305::new(&key.into()))
+}
+
+fn seal(
+    cipher: &ChaCha20Poly1305,
+    msg: &[u8],
+    aad: &[u8],
+) -> Result<EncryptedEntry, KeyStoreError> {
+    let mut nonce = [0u8; NONCE_SIZE];
+    OsRng.fill_bytes(&mut nonce);
+    let ciphertext = cipher
+        .encrypt(Nonce::from_slice(&nonce), Payload { msg, aad })
+        .map_err(|_| KeyStoreError::Decryption)?;
+
+    Ok(EncryptedEntry {
+        nonce: hex::encode(nonce),
+        ciphertext: hex::encode(ciphertext),
+    })
+}
+
+fn open(
+    cipher: &ChaCha20Poly1305,
+    sealed: &EncryptedEntry,
+    aad: &[u8],
+) -> Result<Vec<u8>, KeyStoreError> {
+    let nonce = decode_hex(&sealed.nonce)?;
+    if nonce.len() != NONCE_SIZE {
+        return Err(KeyStoreError::Format("nonce must be 12 bytes".to_string()));
+    }
+    let ciphertext = decode_hex(&sealed.ciphertext)?;
+
+    cipher
+        .decrypt(
+            Nonce::from_slice(&nonce),
+            Payload {
+                msg: &ciphertext,
+                aad,
+            },
+        )
+        .map_err(|_| KeyStoreError::Decryption)
+}
+
+fn decode_hex(value: &str) -> Result<Vec<u8>, KeyStoreError> {
+    hex::decode(value).map_err(|err| KeyStoreError::Format(err.to_string()))
+}
@@ -0,0 +1,408 @@
+//! On-disk node store: every level's hashes plus leaf data in one file
+//!
+//! Opening a store reads only the header; hashes and leaf data are read on
+//! demand, so proofs for trees larger than memory touch `4 * depth` hashes.
+//!
+//! Layout (v1): magic `QTNS`, version, depth, then all node hashes level by
+//! level from the root (node `i` of level `l` at hash slot `(4^l - 1) / 3 + i`),
+//! then one `(offset: u64, len: u32)` entry per leaf into the leaf data that
+//! follows. `len == u32::MAX` marks a leaf whose preimage is unknown.
+//!
+//! `StoreWriter` produces the same file from leaves streamed in order, so a
+//! store can be written without holding the tree either.
+
+use std::fmt;
+use std::fs::File;
+use std::io::{self, BufWriter, Read, Seek, SeekFrom, Write};
+use std::path::Path;
+
+use quad_tree_core::{
+    default_hashes_with, depth_for_leaf_count, HashScheme, LevelSink, QuadHasher, QuadTreeIndex,
+    QuadTreeMembershipProof, StreamingRootBuilder,
+};
+
+use crate::QuadTree;
+
+const STORE_MAGIC: [u8; 4] = *b"QTNS";
+const STORE_VERSION: u8 = 1;
+const HEADER_SIZE: u64 = STORE_MAGIC.len() as u64 + 2;
+const LEAF_ENTRY_SIZE: u64 = 8 + 4;
+const NO_LEAF_DATA: u32 = u32::MAX;
+/// Deepest tree whose hash slots and leaf index end below `u64::MAX` bytes
+/// (at depth 30 the hash slots alone need `level_start(31) * 32 > u64::MAX`)
+const MAX_DEPTH: u8 = 29;
+
+#[derive(Debug)]
+pub(crate) enum StoreError {
+    Io(io::Error),
+    Format(String),
+    /// Path or leaf number outside the stored tree
+    OutOfRange,
+}
+
+impl fmt::Display for StoreError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::Io(err) => write!(f, "node store I/O error: {}", err),
+            Self::Format(msg) => write!(f, "malformed node store: {}", msg),
+            Self::OutOfRange => write!(f, "leaf is outside the stored tree"),
+        }
+    }
+}
+
+impl std::error::Error for StoreError {}
+
+impl From<io::Error> for StoreError {
+    fn from(err: io::Error) -> Self {
+        Self::Io(err)
+    }
+}
+
+/// Number of nodes in all levels above `level`
+fn level_start(level: u8) -> u64 {
+    ((1u64 << (2 * level as u32)) - 1) / 3
+}
+
+/// File offset of the leaf index of a store of `depth`
+fn index_start(depth: u8) -> u64 {
+    HEADER_SIZE + level_start(depth + 1) * 32
+}
+
+/// File offset of the leaf data of a store of `depth`
+fn data_start(depth: u8) -> u64 {
+    index_start(depth) + (1u64 << (2 * depth as u32)) * LEAF_ENTRY_SIZE
+}
+
+fn check_depth(depth: u8) -> Result<(), StoreError> {
+    if depth > MAX_DEPTH {
+        return Err(StoreError::Format(format!(
+            "depth {} exceeds {}",
+            depth, MAX_DEPTH
+        )));
+    }
+    Ok(())
+}
+
+/// Length entry for leaf data, which must stay below the `NO_LEAF_DATA` marker
+fn leaf_data_len(data: &[u8]) -> Result<u32, StoreError> {
+    u32::try_from(data.len())
+        .ok()
+        .filter(|&len| len != NO_LEAF_DATA)
+        .ok_or_else(|| StoreError::Format("leaf data too large".to_string()))
+}
+
+fn write_leaf_entry(out: &mut impl Write, offset: u64, len: u32) -> io::Result<()> {
+    out.write_all(&offset.to_le_bytes())?;
+    out.write_all(&len.to_le_bytes())
+}
+
+/// A tree saved by `NodeStore::save`, read lazily from disk
+pub(crate) struct NodeStore {
+    file: File,
+    depth: u8,
+}
+
+impl NodeStore {
+    /// Write every level of `tree` and its leaf data to `file`
+    pub(crate) fn save<H: QuadHasher>(
+        tree: &QuadTree<H>,
+        file: impl AsRef<Path>,
+    ) -> Result<(), StoreError> {
+        check_depth(tree.depth())?;
+        let mut out = BufWriter::new(File::create(file)?);
+        out.write_all(&STORE_MAGIC)?;
+        out.write_all(&[STORE_VERSION, tree.depth()])?;
+
+        // The in-memory layout is already level-ordered from the root
+        for hash in tree.nodes() {
+            out.write_all(hash)?;
+        }
+        let mut offset = 0u64;
+        for number in 0..tree.leaves().len() {
+            let len = match tree.leaf_data(number) {
+                Some(data) => leaf_data_len(data)?,
+                None => NO_LEAF_DATA,
+            };
+            write_leaf_entry(&mut out, offset, len)?;
+            if len != NO_LEAF_DATA {
+                offset += len as u64;
+            }
+        }
+        for data in (0..tree.leaves().len()).filter_map(|number| tree.leaf_data(number)) {
+            out.write_all(data)?;
+        }
+        out.flush()?;
+        Ok(())
+    }
+
+    /// Open a store, reading only its header
+    pub(crate) fn open(file: impl AsRef<Path>) -> Result<Self, StoreError> {
+        let mut file = File::open(file)?;
+        let mut header = [0u8; HEADER_SIZE as usize];
+        file.read_exact(&mut header)?;
+        if header[..STORE_MAGIC.len()] != STORE_MAGIC {
+            return Err(StoreError::Format("not a quad tree node store".to_string()));
+        }
+        let version = header[STORE_MAGIC.len()];
+        if version != STORE_VERSION {
+            return Err(StoreError::Format(format!(
+                "unsupported version {}",
+                version
+            )));
+        }
+        let depth = header[STORE_MAGIC.len() + 1];
+        check_depth(depth)?;
+
+        let store = Self { file, depth };
+        if store.file.metadata()?.len() < data_start(depth) {
+            return Err(StoreError::Format("file is truncated".to_string()));
+        }
+        Ok(store)
+    }
+
+    pub(crate) fn depth(&self) -> u8 {
+        self.depth
+    }
+
+    pub(crate) fn leaf_count(&self) -> u64 {
+        1u64 << (2 * self.depth as u32)
+    }
+
+    pub(crate) fn root(&self) -> Result<[u8; 32], StoreError> {
+        self.node_hash(0, 0)
+    }
+
+    /// Hash of node `index` (left to right) at `level` (0 is the root)
+    pub(crate) fn node_hash(&self, level: u8, index: u64) -> Result<[u8; 32], StoreError> {
+        if level > self.depth || index >= 1u64 << (2 * level as u32) {
+            return Err(StoreError::OutOfRange);
+        }
+        let mut hash = [0u8; 32];
+        self.read_at(HEADER_SIZE + (level_start(level) + index) * 32, &mut hash)?;
+        Ok(hash)
+    }
+
+    /// Preimage of leaf `leaf_number`, or `None` if only its hash was stored
+    pub(crate) fn leaf_data(&self, leaf_number: u64) -> Result<Option<Vec<u8>>, StoreError> {
+        if leaf_number >= self.leaf_count() {
+            return Err(StoreError::OutOfRange);
+        }
+        let mut entry = [0u8; LEAF_ENTRY_SIZE as usize];
+        self.read_at(
+            index_start(self.depth) + leaf_number * LEAF_ENTRY_SIZE,
+            &mut entry,
+        )?;
+        let (offset, len) = entry.split_at(8);
+        let offset = u64::from_le_bytes(offset.try_into().expect("8-byte offset"));
+        let len = u32::from_le_bytes(len.try_into().expect("4-byte length"));
+        if len == NO_LEAF_DATA {
+            return Ok(None);
+        }
+
+        let data_offset = data_start(self.depth)
+            .checked_add(offset)
+            .ok_or_else(|| StoreError::Format("leaf data offset overflows".to_string()))?;
+        let mut data = vec![0u8; len as usize];
+        self.read_at(data_offset, &mut data)?;
+        Ok(Some(data))
+    }
+
+    /// Membership proof for the leaf at `leaf_path`, reading the
+    /// `depth` sibling groups from disk
+    /// Sibling hashes are stored from LEAF to ROOT (bottom to top)
+    pub(crate) fn generate_membership_proof(
+        &self,
+        leaf_path: &[u8],
+    ) -> Result<QuadTreeMembershipProof, StoreError> {
+        let leaf_index = QuadTreeIndex::try_new(self.depth, leaf_path.to_vec())
+            .map_err(|_| StoreError::OutOfRange)?;
+        let mut position = leaf_index.leaf_number();
+        let leaf_hash = self.node_hash(self.depth, position)?;
+
+        let mut sibling_hashes = Vec::with_capacity(self.depth as usize);
+        for level in (1..=self.depth).rev() {
+            // The 4 children of one parent are adjacent, so read them together
+            let mut group = [0u8; 4 * 32];
+            let first = position & !3;
+            self.read_at(HEADER_SIZE + (level_start(level) + first) * 32, &mut group)?;
+
+            // Collect the 3 sibling hashes in ascending order, skipping our branch
+            let branch = (position & 3) as usize;
+            let mut siblings = [[0u8; 32]; 3];
+            let others = group
+                .chunks_exact(32)
+                .enumerate()
+                .filter(|&(i, _)| i != branch);
+            for (sibling, (_, hash)) in siblings.iter_mut().zip(others) {
+                sibling.copy_from_slice(hash);
+            }
+            sibling_hashes.push(siblings);
+            position >>= 2;
+        }
+
+        Ok(QuadTreeMembershipProof {
+            leaf_index,
+            leaf_hash,
+            sibling_hashes,
+            root_hash: self.root()?,
+        })
+    }
+
+    fn read_at(&self, offset: u64, buf: &mut [u8]) -> Result<(), StoreError> {
+        let mut file = &self.file;
+        file.seek(SeekFrom::Start(offset))?;
+        file.read_exact(buf)?;
+        Ok(())
+    }
+}
+
+/// Writes a node store from leaves pushed left to right, holding O(depth) hashes
+///
+/// The leaf count fixes the depth up front, so every hash has a known slot:
+/// each level gets its own file handle, appended to as `StreamingRootBuilder`
+/// completes its nodes. `finish` fills the slots right of the last leaf with
+/// empty subtrees, giving the same bytes as `NodeStore::save`.
+pub(crate) struct StoreWriter<H: QuadHasher = HashScheme> {
+    builder: StreamingRootBuilder<H, LevelWriters>,
+    hasher: H,
+    depth: u8,
+    leaf_count: u64,
+    leaf_index: BufWriter<File>,
+    leaf_data: BufWriter<File>,
+    /// Bytes of leaf data written so far, the offset of the next leaf's data
+    data_len: u64,
+}
+
+impl<H: QuadHasher> StoreWriter<H> {
+    /// Create `file` for exactly `leaf_count` leaves hashed with `hasher`
+    pub(crate) fn create(
+        file: impl AsRef<Path>,
+        leaf_count: u64,
+        hasher: H,
+    ) -> Result<Self, StoreError> {
+        let depth = depth_for_leaf_count(leaf_count);
+        check_depth(depth)?;
+        let file = file.as_ref();
+        let mut header = File::create(file)?;
+        header.write_all(&STORE_MAGIC)?;
+        header.write_all(&[STORE_VERSION, depth])?;
+
+        let levels = (0..=depth)
+            .map(|height| open_at(file, HEADER_SIZE + level_start(depth - height) * 32))
+            .collect::<Result<_, _>>()?;
+        let sink = LevelWriters {
+            levels,
+            written: vec![0; depth as usize + 1],
+            error: None,
+        };
+        Ok(Self {
+            builder: StreamingRootBuilder::with_sink(hasher, sink),
+            hasher,
+            depth,
+            leaf_count,
+            leaf_index: open_at(file, index_start(depth))?,
+            leaf_data: open_at(file, data_start(depth))?,
+            data_len: 0,
+        })
+    }
+
+    pub(crate) fn depth(&self) -> u8 {
+        self.depth
+    }
+
+    /// Push the next leaf and store `data` as its preimage
+    pub(crate) fn push_leaf(&mut self, data: &[u8]) -> Result<(), StoreError> {
+        self.check_room()?;
+        let len = leaf_data_len(data)?;
+        write_leaf_entry(&mut self.leaf_index, self.data_len, len)?;
+        self.leaf_data.write_all(data)?;
+        self.data_len += len as u64;
+        self.builder.push_leaf(data);
+        Ok(())
+    }
+
+    /// Push the next leaf by hash, with no preimage stored
+    pub(crate) fn push_leaf_hash(&mut self, leaf_hash: [u8; 32]) -> Result<(), StoreError> {
+        self.check_room()?;
+        write_leaf_entry(&mut self.leaf_index, self.data_len, NO_LEAF_DATA)?;
+        self.builder.push_leaf_hash(leaf_hash);
+        Ok(())
+    }
+
+    /// Fill the empty slots, flush every handle and return the root
+    pub(crate) fn finish(self) -> Result<[u8; 32], StoreError> {
+        if self.builder.leaf_count() != self.leaf_count {
+            return Err(StoreError::Format(format!(
+                "{} leaves pushed, {} announced",
+                self.builder.leaf_count(),
+                self.leaf_count
+            )));
+        }
+        let Self {
+            builder,
+            hasher,
+            depth,
+            leaf_count,
+            mut leaf_index,
+            mut leaf_data,
+            data_len,
+        } = self;
+        let (root, mut sink) = builder.finish_with_sink();
+        if let Some(err) = sink.error.take() {
+            return Err(err.into());
+        }
+
+        // The builder reports every node up to the last leaf's groups; the
+        // slots after them hold empty subtrees
+        let empty = default_hashes_with(depth, hasher);
+        for (height, level) in sink.levels.iter_mut().enumerate() {
+            let slots = 1u64 << (2 * (depth as usize - height));
+            for _ in sink.written[height]..slots {
+                level.write_all(&empty[height])?;
+            }
+            level.flush()?;
+        }
+        for _ in leaf_count..1u64 << (2 * depth as u32) {
+            write_leaf_entry(&mut leaf_index, data_len, NO_LEAF_DATA)?;
+        }
+        leaf_index.flush()?;
+        leaf_data.flush()?;
+        Ok(root)
+    }
+
+    fn check_room(&self) -> Result<(), StoreError> {
+        if self.builder.leaf_count() == self.leaf_count {
+            return Err(StoreError::Format(format!(
+                "more than the {} announced leaves",
+                self.leaf_count
+            )));
+        }
+        Ok(())
+    }
+}
+
+/// A write handle on `file` positioned at `offset`, independent of other handles
+fn open_at(file: &Path, offset: u64) -> Result<BufWriter<File>, StoreError> {
+    let mut handle = File::options().write(true).open(file)?;
+    handle.seek(SeekFrom::Start(offset))?;
+    Ok(BufWriter::new(handle))
+}
+
+/// One handle per height (0 = leaves), each starting at its level's first slot
+struct LevelWriters {
+    levels: Vec<BufWriter<File>>,
+    /// Nodes written so far at each height
+    written: Vec<u64>,
+    /// First write failure; `LevelSink` cannot return errors
+    error: Option<io::Error>,
+}
+
+impl LevelSink for LevelWriters {
+    fn node(&mut self, height: u8, _index: u64, hash: &[u8; 32]) {
+        // Nodes of one level arrive in index order, so appending fills the slots in turn
+        if self.error.is_none() {
+            self.error = self.levels[height as usize].write_all(hash).err();
+        }
+        self.written[height as usize] += 1;
+    }
+}
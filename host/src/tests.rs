@@ -1,5 +1,5 @@
 use super::*;
-use quad_tree_core::hash_leaf;
+use quad_tree_core::{hash_leaf, hash_node, Keccak256Hasher, SparseQuadTree, VerifyError};
 
 #[test]
     fn test_membership_proof_depth_1() {
@@ -11,7 +11,7 @@ use quad_tree_core::hash_leaf;
         assert_eq!(proof.leaf_index.depth, 1);
         assert_eq!(proof.leaf_index.path, vec![2]);
         assert_eq!(proof.sibling_hashes.len(), 1);
-        assert_eq!(proof.root_hash, tree.hash);
+        assert_eq!(proof.root_hash, tree.root());
 
         // Verify proof
         assert!(proof.verify(), "Proof should verify");
@@ -53,9 +53,11 @@ use quad_tree_core::hash_leaf;
         let proof = generate_membership_proof(&tree, &[0, 1, 2]);
 
         let calculated_size = proof.size_bytes();
-        let expected_size = 32 + 32 + 1 + 3 + (3 * 3 * 32); // leaf + root + depth + path + siblings
+        // magic + version + depth + packed path + leaf + root + siblings + checksum
+        let expected_size = 4 + 1 + 1 + 1 + 32 + 32 + (3 * 3 * 32) + 4;
 
         assert_eq!(calculated_size, expected_size);
+        assert_eq!(proof.to_bytes().unwrap().len(), expected_size);
     }
 
     #[test]
@@ -110,9 +112,8 @@ use quad_tree_core::hash_leaf;
 
     #[test]
     fn test_deterministic_tree_building() {
-        // Note: This test will fail because ML-KEM uses random key generation
-        // In production, you'd use a deterministic seed for reproducible trees
-        // This test documents the expected behavior
+        // Unseeded builds draw ML-KEM keys from OsRng
+        // Reproducible trees use build_quad_tree_seeded (see below)
 
         let tree1 = build_quad_tree(1);
         let tree2 = build_quad_tree(1);
@@ -120,11 +121,27 @@ use quad_tree_core::hash_leaf;
         // Roots will be different due to random key generation
         // This is EXPECTED and SECURE behavior
         assert_ne!(
-            tree1.hash, tree2.hash,
+            tree1.root(), tree2.root(),
             "Trees should differ due to random ML-KEM key generation"
         );
     }
 
+    #[test]
+    fn test_seeded_tree_building_is_reproducible() {
+        let tree1 = build_quad_tree_seeded(2, &[7u8; 32]);
+        let tree2 = build_quad_tree_seeded(2, &[7u8; 32]);
+        let other_seed = build_quad_tree_seeded(2, &[8u8; 32]);
+
+        assert_eq!(tree1.root(), tree2.root(), "Same seed must give the same root");
+        assert_eq!(
+            generate_key_membership_proof(&tree1, &[3, 0]).encapsulation_key,
+            generate_key_membership_proof(&tree2, &[3, 0]).encapsulation_key
+        );
+        assert_ne!(tree1.root(), other_seed.root());
+        // Every leaf gets its own key
+        assert_ne!(tree1.leaf_hash(&[0, 0]), tree1.leaf_hash(&[0, 1]));
+    }
+
     #[test]
     fn test_hash_collision_resistance() {
         // Generate two different leaves and verify different hashes
@@ -142,25 +159,507 @@ use quad_tree_core::hash_leaf;
 
     #[test]
     fn test_quaternary_property() {
-        // Verify that each internal node has exactly 4 children
+        // Every internal node hashes exactly the 4 adjacent nodes below it
+        let tree = build_quad_tree(2);
+        assert_eq!(tree.nodes().len(), 1 + 4 + 16);
+        assert_eq!(tree.leaves().len(), 16);
+
+        for level in 0..tree.depth() {
+            let parents = &tree.nodes()[level_offset(level)..level_offset(level + 1)];
+            let children = &tree.nodes()[level_offset(level + 1)..level_offset(level + 2)];
+            for (parent, group) in parents.iter().zip(children.chunks_exact(4)) {
+                assert_eq!(*parent, hash_node(&group[0], &group[1], &group[2], &group[3]));
+            }
+        }
+    }
+
+    #[test]
+    fn test_update_leaf_recomputes_root() {
+        let mut tree = build_quad_tree(2);
+        let old_root = tree.root();
+
+        // Rotate the key at [1, 2]
+        let new_leaf = hash_leaf(&generate_leaf(&[1, 2], None, None));
+        let new_root = tree.update(&[1, 2], new_leaf).unwrap();
+
+        assert_ne!(new_root, old_root);
+        assert_eq!(new_root, tree.root());
+        assert_eq!(tree.leaf_hash(&[1, 2]), Some(new_leaf));
+
+        // Proofs for the rotated leaf and an untouched leaf both verify
+        let proof = generate_membership_proof(&tree, &[1, 2]);
+        assert_eq!(proof.leaf_hash, new_leaf);
+        assert!(proof.verify(), "Proof for updated leaf should verify");
+        assert!(generate_membership_proof(&tree, &[3, 0]).verify());
+    }
+
+    #[test]
+    fn test_remove_and_insert_leaf() {
+        let mut tree = build_quad_tree(2);
+        let original_root = tree.root();
+        let original_leaf = tree.leaf_hash(&[0, 3]).unwrap();
+
+        assert!(tree.insert(&[0, 3], [7u8; 32]).is_none(), "Slot is occupied");
+
+        tree.remove(&[0, 3]).unwrap();
+        assert_eq!(tree.leaf_hash(&[0, 3]), Some(EMPTY_LEAF_HASH));
+        assert!(tree.remove(&[0, 3]).is_none(), "Slot is already empty");
+        assert!(tree.update(&[0, 3], [7u8; 32]).is_none(), "Slot is empty");
+
+        // Re-inserting the original leaf restores the original root
+        let root = tree.insert(&[0, 3], original_leaf).unwrap();
+        assert_eq!(root, original_root);
+    }
+
+    #[test]
+    fn test_registry_rejects_paths_outside_tree() {
+        let mut tree = build_quad_tree(2);
+        let root = tree.root();
+
+        assert_eq!(tree.leaf_hash(&[1]), None, "Path stops above the leaves");
+        assert_eq!(tree.leaf_hash(&[1, 2, 3]), None, "Path runs past the leaves");
+        assert!(tree.insert(&[0, 4], [7u8; 32]).is_none(), "Digit above 3");
+        assert!(tree.update(&[4, 0], [7u8; 32]).is_none());
+        assert!(tree.remove(&[]).is_none());
+        assert_eq!(tree.root(), root);
+    }
+
+    #[test]
+    fn test_multi_proof_matches_tree() {
+        let tree = build_quad_tree(2);
+        let paths = vec![vec![0, 1], vec![0, 2], vec![3, 3]];
+
+        let multi_proof = generate_multi_proof(&tree, &paths);
+
+        assert_eq!(multi_proof.root_hash, tree.root());
+        assert_eq!(multi_proof.leaves.len(), 3);
+        assert!(multi_proof.sibling_hashes.len() < paths.len() * 2 * 3);
+        assert!(multi_proof.verify(), "Batch proof should verify");
+    }
+
+    #[test]
+    fn test_key_membership_proof() {
+        let tree = build_quad_tree(2);
+        let proof = generate_key_membership_proof(&tree, &[2, 1]);
+
+        assert_eq!(proof.encapsulation_key.len(), 1184);
+        assert_eq!(Some(hash_leaf(&proof.encapsulation_key)), tree.leaf_hash(&[2, 1]));
+        assert!(proof.verify(), "Key membership proof should verify");
+    }
+
+    #[cfg(feature = "poseidon2")]
+    #[test]
+    fn test_poseidon2_proof_same_leaves() {
+        let tree = build_quad_tree(2);
+        let proof = generate_poseidon2_proof(&tree, &[1, 3]);
+
+        assert_ne!(proof.root_hash, tree.root());
+        let permutation = Poseidon2Hasher::permutation();
+        assert!(proof.verify_with(Poseidon2Hasher::new(&permutation, HashScheme::Legacy)));
+        assert!(!proof.verify(), "Poseidon2 proof must not verify under SHA3");
+    }
+
+    #[test]
+    fn test_build_from_supplied_leaves() {
+        let keys: Vec<Vec<u8>> = (0..6u8).map(|i| vec![i; 1184]).collect();
+        let tree = build_quad_tree_from_leaves(&keys);
+
+        // 6 leaves pad to depth 2, matching the core sparse builder
+        assert_eq!(tree.root(), SparseQuadTree::from_leaves(&keys).root());
+        assert_eq!(tree.leaf_hash(&[1, 1]), Some(hash_leaf(&keys[5])));
+        assert_eq!(tree.leaf_hash(&[1, 2]), Some(EMPTY_LEAF_HASH));
+
+        let proof = generate_key_membership_proof(&tree, &[0, 3]);
+        assert_eq!(proof.encapsulation_key, keys[3]);
+
+        let hashed = build_quad_tree_from_leaf_hashes(keys.iter().map(|key| hash_leaf(key)));
+        assert_eq!(hashed.root(), tree.root());
+        assert!(generate_membership_proof(&hashed, &[1, 0]).verify());
+    }
+
+    #[test]
+    fn test_tree_with_other_hashers() {
+        let keys: Vec<Vec<u8>> = (0..6u8).map(|i| vec![i; 64]).collect();
+
+        let v1 = build_quad_tree_from_leaves_with(&keys, HashScheme::V1);
+        assert_eq!(v1.root(), SparseQuadTree::from_leaves_with(&keys, HashScheme::V1).root());
+        assert_ne!(v1.root(), build_quad_tree_from_leaves(&keys).root());
+        let proof = generate_membership_proof(&v1, &[0, 2]);
+        assert!(proof.verify_with(HashScheme::V1));
+        assert!(!proof.verify(), "V1 proof must not verify under the legacy scheme");
+
+        let keccak = Keccak256Hasher(HashScheme::V1);
+        let mut tree = build_quad_tree_from_leaves_with(&keys, keccak);
+        let mut sparse = SparseQuadTree::from_leaves_with(&keys, keccak);
+        assert_eq!(tree.root(), sparse.root());
+        let rotated = keccak.hash_leaf(b"rotated");
+        sparse.insert(&[0, 1], rotated);
+        assert_eq!(tree.update(&[0, 1], rotated), Some(sparse.root()));
+        assert!(generate_multi_proof(&tree, &[vec![0, 1], vec![1, 0]]).verify_with(keccak));
+    }
+
+    #[test]
+    fn test_keystore_round_trip() {
+        let mut keystore = KeyStore::new("correct horse").unwrap();
+        let tree = build_quad_tree_with_keystore(1, Some(&[3u8; 32]), &mut keystore);
+        assert_eq!(keystore.len(), 4);
+        // Storing keys does not change the tree
+        assert_eq!(tree.root(), build_quad_tree_seeded(1, &[3u8; 32]).root());
+
+        let file = std::env::temp_dir().join(format!("quad_keystore_{}.json", std::process::id()));
+        keystore.save(&file).unwrap();
+        assert!(matches!(
+            KeyStore::load(&file, "wrong passphrase"),
+            Err(keystore::KeyStoreError::Decryption)
+        ));
+        let reloaded = KeyStore::load(&file, "correct horse").unwrap();
+        std::fs::remove_file(&file).unwrap();
+
+        let proof = generate_key_membership_proof(&tree, &[2]);
+        let encoded_key = proof.encapsulation_key.as_slice().try_into().unwrap();
+        let encapsulation_key = <MlKem768 as KemCore>::EncapsulationKey::from_bytes(&encoded_key);
+        let (ciphertext, sender_secret) = encapsulation_key.encapsulate(&mut OsRng).unwrap();
+
+        let member_secret = reloaded.decapsulate(&[2], &ciphertext).unwrap();
+        assert_eq!(member_secret.as_slice(), &sender_secret[..]);
+        assert!(matches!(
+            reloaded.decapsulation_key(&[0, 0]),
+            Err(keystore::KeyStoreError::UnknownLeaf(_))
+        ));
+    }
+
+    #[test]
+    fn test_read_proof_file_formats() {
+        let tree = build_quad_tree(2);
+        let proof = generate_membership_proof(&tree, &[3, 1]);
+        let dir = std::env::temp_dir();
+        let stem = format!("quad_proof_{}", std::process::id());
+
+        let json = serde_json::to_string_pretty(&proof).unwrap();
+        assert!(json.contains("\"leaf_index\": \"31\""));
+        let files = [
+            (dir.join(format!("{}.json", stem)), json.into_bytes()),
+            (dir.join(format!("{}.bin", stem)), bincode::serialize(&proof).unwrap()),
+            (dir.join(format!("{}.qtmp", stem)), proof.to_bytes().unwrap()),
+        ];
+
+        for (file, contents) in &files {
+            std::fs::write(file, contents).unwrap();
+            let loaded = read_proof_file(file).unwrap();
+            std::fs::remove_file(file).unwrap();
+
+            assert_eq!(loaded.leaf_index, proof.leaf_index);
+            assert_eq!(loaded.root_hash, tree.root());
+            assert!(loaded.verify());
+        }
+    }
+
+    #[test]
+    fn test_verify_detailed_reports_tampering() {
         let tree = build_quad_tree(2);
+        let proof = generate_membership_proof(&tree, &[1, 2]);
+        assert!(proof.verify_detailed().is_ok());
+
+        let mut wrong_root = proof.clone();
+        wrong_root.root_hash[0] ^= 0xFF;
+        let err = wrong_root.verify_detailed().unwrap_err();
+        assert!(matches!(err, VerifyError::RootMismatch { .. }));
+        assert!(err.to_string().contains("does not match expected root"));
+
+        let mut truncated = proof;
+        truncated.sibling_hashes.pop();
+        assert_eq!(
+            truncated.verify_detailed(),
+            Err(VerifyError::SiblingCountMismatch {
+                depth: 2,
+                sibling_levels: 1
+            })
+        );
+    }
 
-        fn verify_quaternary(node: &QuadTreeNode) -> bool {
-            if let Some(ref children) = node.children {
-                if children.len() != 4 {
-                    return false;
-                }
-                for child in children.iter() {
-                    if !verify_quaternary(child) {
-                        return false;
-                    }
-                }
+    #[test]
+    fn test_verify_against_tree_root() {
+        let tree = build_quad_tree(2);
+        let other_tree = build_quad_tree(2);
+        let proof = generate_membership_proof(&tree, &[0, 3]);
+
+        assert!(proof.verify_against(&tree.root()));
+        assert!(!proof.verify_against(&other_tree.root()));
+
+        // Input for quad-guest-trusted-root decodes back to the same pair
+        let input = bincode::serialize(&(tree.root(), &proof)).unwrap();
+        let (root, decoded): ([u8; 32], QuadTreeMembershipProof) =
+            bincode::deserialize(&input).unwrap();
+        assert_eq!(root, tree.root());
+        assert!(decoded.verify_against(&root));
+    }
+
+    #[test]
+    fn test_cli_parse_path() {
+        assert_eq!(cli::parse_path("012").unwrap(), vec![0, 1, 2]);
+        assert_eq!(cli::parse_path("3, 1,0").unwrap(), vec![3, 1, 0]);
+        assert!(cli::parse_path("014").is_err());
+    }
+
+    #[test]
+    fn test_cli_commands() {
+        let dir = std::env::temp_dir();
+        let stem = format!("quad_cli_{}", std::process::id());
+        let tree_file = dir.join(format!("{}_tree.json", stem));
+        let proof_file = dir.join(format!("{}.qtmp", stem));
+
+        let tree = build_quad_tree_from_leaves([b"alice".as_slice(), b"bob", b"carol"]);
+        cli::save_tree(&tree, &tree_file).unwrap();
+        assert_eq!(cli::load_tree(&tree_file).unwrap().root(), tree.root());
+
+        let prove = cli::Cli::try_parse_from([
+            "quad-host",
+            "prove",
+            "--tree",
+            tree_file.to_str().unwrap(),
+            "--index",
+            "1",
+            "--out",
+            proof_file.to_str().unwrap(),
+        ])
+        .unwrap();
+        cli::run(prove.command.unwrap()).unwrap();
+
+        let proof = read_proof_file(&proof_file).unwrap();
+        assert_eq!(proof.leaf_index.path, vec![1]);
+        assert_eq!(proof.leaf_hash, hash_leaf(b"bob"));
+        assert_eq!(proof.root_hash, tree.root());
+
+        let verify = |root: &str| {
+            let mut args = vec!["quad-host", "verify", proof_file.to_str().unwrap()];
+            if !root.is_empty() {
+                args.extend(["--root", root]);
             }
-            true
+            cli::run(cli::Cli::try_parse_from(args).unwrap().command.unwrap())
+        };
+        assert!(verify("").is_ok());
+        assert!(verify(&hex::encode(tree.root())).is_ok());
+        let err = verify(&hex::encode([7u8; 32])).unwrap_err();
+        assert_eq!(err.exit_code(), std::process::ExitCode::from(1));
+
+        // A path that does not match the tree depth is an input error
+        let bad_path = cli::Cli::try_parse_from([
+            "quad-host",
+            "prove",
+            "--tree",
+            tree_file.to_str().unwrap(),
+            "--path",
+            "01",
+            "--out",
+            proof_file.to_str().unwrap(),
+        ])
+        .unwrap();
+        let err = cli::run(bad_path.command.unwrap()).unwrap_err();
+        assert_eq!(err.exit_code(), std::process::ExitCode::from(2));
+
+        // A short seed is reported as a seed, not a hash
+        let bad_seed = cli::Cli::try_parse_from([
+            "quad-host",
+            "build",
+            "--depth",
+            "1",
+            "--seed",
+            "abcd",
+            "--out",
+            tree_file.to_str().unwrap(),
+        ])
+        .unwrap();
+        let err = cli::run(bad_seed.command.unwrap()).unwrap_err();
+        assert_eq!(err.to_string(), "--seed must be 32 bytes (64 hex digits)");
+
+        // root checks the leaves against the stored root before printing it
+        let contents = std::fs::read_to_string(&tree_file).unwrap();
+        let tampered = contents.replace(&hex::encode(tree.root()), &hex::encode([7u8; 32]));
+        std::fs::write(&tree_file, tampered).unwrap();
+        let root_args = ["quad-host", "root", "--tree", tree_file.to_str().unwrap()];
+        let root = cli::Cli::try_parse_from(root_args).unwrap();
+        let err = cli::run(root.command.unwrap()).unwrap_err();
+        assert_eq!(err.exit_code(), std::process::ExitCode::from(2));
+        assert!(err.to_string().ends_with("leaves do not hash to the stored root"));
+
+        // The root is compared as bytes, so an upper-case root still loads
+        let upper = contents.replace(&hex::encode(tree.root()), &hex::encode_upper(tree.root()));
+        std::fs::write(&tree_file, upper).unwrap();
+        assert_eq!(cli::load_tree(&tree_file).unwrap().root(), tree.root());
+
+        // Leaf data that no longer matches its hash is caught though hashes and root are intact
+        let data_field = |data: &[u8]| format!("\"data\":\"{}\"", hex::encode(data));
+        let tampered = contents.replace(&data_field(b"bob"), &data_field(b"eve"));
+        assert_ne!(tampered, contents);
+        std::fs::write(&tree_file, tampered).unwrap();
+        let err = cli::load_tree(&tree_file).map(|_| ()).unwrap_err();
+        assert!(err.to_string().ends_with("data of leaf 1 does not hash to its stored hash"));
+
+        std::fs::remove_file(&tree_file).unwrap();
+        std::fs::remove_file(&proof_file).unwrap();
+    }
+
+    #[test]
+    fn test_cli_registry_commands() {
+        let tree_file =
+            std::env::temp_dir().join(format!("quad_registry_{}.json", std::process::id()));
+        let mut tree = build_quad_tree_from_leaves([b"alice".as_slice(), b"bob", b"carol"]);
+        cli::save_tree(&tree, &tree_file).unwrap();
+
+        let edit = |args: &[&str]| {
+            let mut argv = vec!["quad-host", args[0], "--tree", tree_file.to_str().unwrap()];
+            argv.extend_from_slice(&args[1..]);
+            cli::run(cli::Cli::try_parse_from(argv).unwrap().command.unwrap())
+        };
+
+        // Rotate bob's key, fill the empty slot 3 by hash, then remove alice
+        let dave = hex::encode(b"dave");
+        let erin = hex::encode(hash_leaf(b"erin"));
+        edit(&["update", "--path", "1", "--leaf", &dave]).unwrap();
+        edit(&["insert", "--path", "3", "--leaf", &erin, "--hashed"]).unwrap();
+        edit(&["remove", "--path", "0"]).unwrap();
+        tree.update_leaf(&[1], b"dave".to_vec()).unwrap();
+        tree.insert(&[3], hash_leaf(b"erin")).unwrap();
+        let root = tree.remove(&[0]).unwrap();
+
+        let edited = cli::load_tree(&tree_file).unwrap();
+        assert_eq!(edited.root(), root);
+        assert_eq!(edited.leaf_data(1), Some(b"dave".as_slice()));
+        assert_eq!(edited.leaf_data(3), None);
+
+        // Slots in the wrong state and paths of the wrong depth leave the file alone
+        let err = edit(&["insert", "--path", "2", "--leaf", &dave]).unwrap_err();
+        assert_eq!(err.to_string(), "leaf 2 is already occupied");
+        let err = edit(&["update", "--path", "0", "--leaf", &dave]).unwrap_err();
+        assert_eq!(err.to_string(), "leaf 0 is empty");
+        assert!(edit(&["remove", "--path", "0"]).is_err());
+        let err = edit(&["remove", "--path", "01"]).unwrap_err();
+        assert_eq!(err.exit_code(), std::process::ExitCode::from(2));
+        assert_eq!(cli::load_tree(&tree_file).unwrap().root(), root);
+
+        std::fs::remove_file(&tree_file).unwrap();
+    }
+
+    #[test]
+    fn test_node_store_proofs_match_tree() {
+        let tree = build_quad_tree_from_leaves((0..20u8).map(|i| vec![i; 3]));
+        let file = std::env::temp_dir().join(format!("quad_store_{}.qtns", std::process::id()));
+        NodeStore::save(&tree, &file).unwrap();
+
+        let store = NodeStore::open(&file).unwrap();
+        assert_eq!(store.depth(), 3);
+        assert_eq!(store.root().unwrap(), tree.root());
+        for index in QuadTreeIndex::leaves(3) {
+            let expected = generate_membership_proof(&tree, &index.path);
+            let proof = store.generate_membership_proof(&index.path).unwrap();
+            assert_eq!(proof.leaf_hash, expected.leaf_hash);
+            assert_eq!(proof.sibling_hashes, expected.sibling_hashes);
+            assert!(proof.verify_against(&tree.root()));
         }
 
-        assert!(
-            verify_quaternary(&tree),
-            "All internal nodes must have exactly 4 children"
+        assert_eq!(store.leaf_data(19).unwrap(), Some(vec![19; 3]));
+        assert_eq!(store.leaf_data(20).unwrap(), None);
+        assert!(matches!(
+            store.generate_membership_proof(&[0, 1]),
+            Err(store::StoreError::OutOfRange)
+        ));
+
+        std::fs::remove_file(&file).unwrap();
+    }
+
+    #[test]
+    fn test_streamed_store_matches_saved_store() {
+        let dir = std::env::temp_dir().join(format!("quad_stream_store_{}", std::process::id()));
+        std::fs::create_dir_all(&dir).unwrap();
+        let saved = dir.join("saved.qtns");
+        let streamed = dir.join("streamed.qtns");
+
+        // Leaf data through the CLI path, which reads the leaf file twice
+        let values: Vec<Vec<u8>> = (0..21u8).map(|i| vec![i; 1 + i as usize % 4]).collect();
+        let lines: Vec<String> = values.iter().map(hex::encode).collect();
+        let leaf_file = dir.join("leaves.txt");
+        std::fs::write(&leaf_file, format!("# members\n{}\n", lines.join("\n"))).unwrap();
+        let tree = build_quad_tree_from_leaves(&values);
+        NodeStore::save(&tree, &saved).unwrap();
+        let root = cli::stream_store(&leaf_file, false, &streamed).unwrap();
+        assert_eq!(root, tree.root());
+        assert_eq!(std::fs::read(&streamed).unwrap(), std::fs::read(&saved).unwrap());
+
+        // Hashed leaves store no preimages; an exact power of four needs no padding
+        let hashes: Vec<[u8; 32]> = (0..16u8).map(|i| hash_leaf(&[i])).collect();
+        let tree = build_quad_tree_from_leaf_hashes(hashes.clone());
+        NodeStore::save(&tree, &saved).unwrap();
+        let mut writer = store::StoreWriter::create(&streamed, 16, HashScheme::Legacy).unwrap();
+        for hash in &hashes {
+            writer.push_leaf_hash(*hash).unwrap();
+        }
+        assert!(writer.push_leaf(&[0]).is_err());
+        assert_eq!(writer.finish().unwrap(), tree.root());
+        assert_eq!(std::fs::read(&streamed).unwrap(), std::fs::read(&saved).unwrap());
+
+        // The announced count is the whole leaf set, so stopping early is an error
+        let writer = store::StoreWriter::create(&streamed, 5, HashScheme::Legacy).unwrap();
+        assert!(writer.finish().is_err());
+
+        std::fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn test_node_store_rejects_overflowing_depth() {
+        let file = std::env::temp_dir().join(format!("quad_depth_{}.qtns", std::process::id()));
+        let open_with_depth = |depth: u8| {
+            std::fs::write(&file, [b'Q', b'T', b'N', b'S', 1, depth]).unwrap();
+            NodeStore::open(&file).err().map(|err| err.to_string())
+        };
+
+        // Depth 29 offsets still fit in a u64, so only the missing hashes are reported
+        assert_eq!(
+            open_with_depth(29).as_deref(),
+            Some("malformed node store: file is truncated")
         );
+        assert_eq!(
+            open_with_depth(30).as_deref(),
+            Some("malformed node store: depth 30 exceeds 29")
+        );
+
+        std::fs::remove_file(&file).unwrap();
+    }
+
+    #[test]
+    fn test_parallel_build_matches_serial() {
+        let serial = build_quad_tree_seeded(3, &[5u8; 32]);
+        let parallel = build_quad_tree_parallel(3, Some(&[5u8; 32]), None);
+
+        assert_eq!(parallel.root(), serial.root());
+        assert_eq!(parallel.nodes(), serial.nodes());
+        assert_eq!(parallel.leaf_data(17), serial.leaf_data(17));
+
+        let mut keystore = KeyStore::new("correct horse").unwrap();
+        let with_keys = build_quad_tree_parallel(1, Some(&[5u8; 32]), Some(&mut keystore));
+        assert_eq!(keystore.len(), 4);
+        assert_eq!(with_keys.root(), build_quad_tree_seeded(1, &[5u8; 32]).root());
+        assert!(keystore.decapsulation_key(&[3]).is_ok());
+    }
+
+    #[test]
+    fn test_stream_root_matches_built_tree() {
+        let dir = std::env::temp_dir().join(format!("quad_stream_{}", std::process::id()));
+        std::fs::create_dir_all(&dir).unwrap();
+        let leaf_file = dir.join("leaves.txt");
+        let values: Vec<Vec<u8>> = (0..21u8).map(|i| vec![i; 5]).collect();
+        let lines: Vec<String> = values.iter().map(hex::encode).collect();
+        std::fs::write(&leaf_file, format!("# members\n{}\n\n", lines.join("\n"))).unwrap();
+
+        let level_dir = dir.join("levels");
+        let root = cli::stream_root(&leaf_file, false, Some(&level_dir)).unwrap();
+        let tree = build_quad_tree_from_leaves(&values);
+        assert_eq!(root, tree.root());
+
+        // Level files hold every group with a real leaf, padding included
+        let leaf_level = std::fs::read(level_dir.join("level_0.bin")).unwrap();
+        assert_eq!(leaf_level.len(), 24 * 32);
+        assert_eq!(&leaf_level[..], tree.leaves()[..24].concat());
+        assert_eq!(std::fs::read(level_dir.join("level_3.bin")).unwrap(), root);
+
+        std::fs::remove_dir_all(&dir).unwrap();
     }